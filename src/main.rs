@@ -1,24 +1,350 @@
 #![allow(dead_code)]
-#![feature(map_first_last)]
 mod rman;
-use ureq;
-use rayon::prelude::*;
+use std::ops::Bound;
 
-fn main() -> Result<(), String> {
-    let dir = "/home/jesus/tmp/lol";
-    let url = "/home/jesus/tmp/lol/manifest.manifest";
+const USAGE: &str = "usage:
+    rman verify <manifest> <dir> [langs] [--fast|--force]
+    rman repair <manifest> <dir> <cdn> [langs] [--fast|--force]
+    rman download <manifest> <dir> <cdn> [langs] [--fast|--force]
+    rman scan <manifest> <dir> [langs]
+    rman prune <manifest> <dir> <langs> [--dry-run]
+    rman diff <old manifest> <new manifest>
+    rman stats <manifest>
+    rman find <manifest> <path|id>
+    rman roundtrip <manifest>
+    rman inspect <manifest> [tree|json]
+    rman export <manifest> <json|ndjson|csv> [manifest|files|chunks|bundles|file_chunks|langs]
+    rman import <manifest json> <output manifest>
+    rman update <old manifest> <new manifest> <dir> <cdn> [langs]
+filters for verify, repair, download and update:
+    --glob <pattern> --regex <pattern> --ext <ext> --min-size <n> --max-size <n> --ignore-case";
+
+fn parse_selector(args: &mut Vec<&str>) -> Result<rman::Selector, rman::Error> {
+    let mut selector = rman::Selector::new();
+    let mut min_size = Bound::Unbounded;
+    let mut max_size = Bound::Unbounded;
+    let mut rest = Vec::with_capacity(args.len());
+    let mut iter = args.drain(..);
+    while let Some(arg) = iter.next() {
+        if arg == "--ignore-case" {
+            selector = selector.ignore_case(true);
+            continue;
+        }
+        if !matches!(
+            arg,
+            "--glob" | "--regex" | "--ext" | "--min-size" | "--max-size"
+        ) {
+            rest.push(arg);
+            continue;
+        }
+        let value = match iter.next() {
+            Some(value) => value,
+            None => {
+                return Err(rman::Error::validation(format!(
+                    "Missing value for {}!",
+                    arg
+                )))
+            }
+        };
+        let size = || {
+            value
+                .parse::<u64>()
+                .map(Bound::Included)
+                .map_err(|_| rman::Error::validation(format!("Bad size {}!", value)))
+        };
+        match arg {
+            "--glob" => selector = selector.glob(value)?,
+            "--regex" => selector = selector.regex(value)?,
+            "--ext" => selector = selector.extension(value),
+            "--min-size" => min_size = size()?,
+            _ => max_size = size()?,
+        }
+    }
+    drop(iter);
+    *args = rest;
+    Ok(selector.size((min_size, max_size)))
+}
+
+fn verify(
+    url: &str,
+    dir: &str,
+    langs: &str,
+    mode: &str,
+    selector: rman::Selector,
+) -> Result<(), rman::Error> {
     let mut agent = ureq::AgentBuilder::new().build();
     let man = rman::Manifest::download(&mut agent, url)?;
-    man.files.par_iter().for_each(|file| {
-        if !file.langs.contains("none") {
-            return;
+    if let Err(err) = man.check_keys() {
+        eprintln!("warning: {}", err);
+    }
+    let langs = man.parse_lang_filter(langs)?;
+    let selection = man.select(&selector.langs(langs));
+    let report = match mode {
+        "--fast" | "--force" => {
+            let mut cache = rman::VerifyCache::load(dir, man.id).force(mode == "--force");
+            let report = selection.verify_with_cache(&mut cache);
+            cache.save()?;
+            report
         }
-        if file.verify(dir) {
-            println!("{} is correct!", &file.name);
-        } else {
-            println!("{} is bad!", &file.name);
+        _ => selection.verify(dir),
+    };
+    println!("{}", report);
+    Ok(())
+}
+
+fn repair(
+    url: &str,
+    dir: &str,
+    cdn: &str,
+    langs: &str,
+    mode: &str,
+    selector: rman::Selector,
+) -> Result<(), rman::Error> {
+    let mut agent = ureq::AgentBuilder::new().build();
+    let man = rman::Manifest::download(&mut agent, url)?;
+    let langs = man.parse_lang_filter(langs)?;
+    let selection = man.select(&selector.langs(langs));
+    let report = match mode {
+        "--fast" | "--force" => {
+            let mut cache = rman::VerifyCache::load(dir, man.id).force(mode == "--force");
+            let report = selection.repair_in_dir_with_cache(&mut cache, &mut agent, cdn)?;
+            cache.save()?;
+            report
         }
-    });
+        _ => selection.repair_in_dir(dir, &mut agent, cdn)?,
+    };
+    for result in report.get_bad_files() {
+        println!("{}", result);
+    }
+    println!(
+        "repaired {} files, {} bad chunks",
+        report.get_bad_files().count(),
+        report.get_bad_chunk_count()
+    );
     Ok(())
 }
 
+fn prune(url: &str, dir: &str, langs: &str, dry_run: bool) -> Result<(), rman::Error> {
+    let mut agent = ureq::AgentBuilder::new().build();
+    let man = rman::Manifest::download(&mut agent, url)?;
+    let langs = man.parse_lang_filter(langs)?;
+    let report = man
+        .select(&rman::Selector::new().langs(langs))
+        .scan_in_dir(dir)?;
+    println!("{}", report);
+    if dry_run {
+        Ok(())
+    } else {
+        report.prune()
+    }
+}
+
+fn download(
+    url: &str,
+    dir: &str,
+    cdn: &str,
+    langs: &str,
+    mode: &str,
+    selector: rman::Selector,
+) -> Result<(), rman::Error> {
+    let mut agent = ureq::AgentBuilder::new().build();
+    let man = rman::Manifest::download(&mut agent, url)?;
+    let langs = man.parse_lang_filter(langs)?;
+    let selection = man.select(&selector.langs(langs));
+    match mode {
+        "--fast" | "--force" => {
+            let cache = rman::VerifyCache::load(dir, man.id).force(mode == "--force");
+            selection.download_in_dir_with_cache(&cache, &mut agent, cdn)?;
+        }
+        _ => selection.download_in_dir(dir, &mut agent, cdn)?,
+    }
+    println!(
+        "downloaded {} files, {} bytes",
+        selection.len(),
+        selection.get_total_size()
+    );
+    Ok(())
+}
+
+fn find(url: &str, path: &str) -> Result<(), rman::Error> {
+    let mut agent = ureq::AgentBuilder::new().build();
+    let man = rman::Manifest::download(&mut agent, url)?;
+    let index = man.index();
+    let mut files = index.get_by_path(path).into_iter().collect::<Vec<_>>();
+    if files.is_empty() {
+        files = index.get_by_path_nocase(path);
+    }
+    if files.is_empty() {
+        if let Ok(id) = u64::from_str_radix(path, 16) {
+            files.extend(index.get_by_id(id));
+        }
+    }
+    if files.is_empty() {
+        return Err(rman::Error::validation("File not found!").with_path(path));
+    }
+    for file in files {
+        println!("{:016X} {}", file.id, file);
+    }
+    Ok(())
+}
+
+fn roundtrip(url: &str) -> Result<(), rman::Error> {
+    let mut agent = ureq::AgentBuilder::new().build();
+    let man = rman::Manifest::download(&mut agent, url)?;
+    man.check_roundtrip()?;
+    println!("manifest {:016X} survives a write and read!", man.id);
+    Ok(())
+}
+
+fn inspect(url: &str, format: &str) -> Result<(), rman::Error> {
+    let inspector = rman::inspect::Inspector::default();
+    let limits = rman::ParseLimits::DEFAULT;
+    let node = if url.starts_with("https://") || url.starts_with("http://") {
+        let agent = ureq::AgentBuilder::new().build();
+        let response = agent.get(url).call().map_err(|error| {
+            rman::Error::network("Failed to request manifest!", error).with_path(url)
+        })?;
+        inspector.inspect_manifest(&mut response.into_reader(), &limits)
+    } else {
+        let mut file = std::fs::File::open(url).map_err(|error| {
+            rman::Error::io("Failed to open manifest file!", error).with_path(url)
+        })?;
+        inspector.inspect_manifest(&mut file, &limits)
+    }
+    .map_err(|error| error.with_path(url))?;
+    match format {
+        "json" => println!("{}", node.to_json()),
+        _ => print!("{}", node.to_tree()),
+    }
+    Ok(())
+}
+
+fn diff(old_url: &str, new_url: &str) -> Result<(), rman::Error> {
+    let mut agent = ureq::AgentBuilder::new().build();
+    let old = rman::Manifest::download(&mut agent, old_url)?;
+    let new = rman::Manifest::download(&mut agent, new_url)?;
+    println!("{}", old.diff(&new));
+    Ok(())
+}
+
+fn stats(url: &str) -> Result<(), rman::Error> {
+    let mut agent = ureq::AgentBuilder::new().build();
+    let man = rman::Manifest::download(&mut agent, url)?;
+    if let Err(err) = man.check_keys() {
+        eprintln!("warning: {}", err);
+    }
+    for problem in &man.dir_problems {
+        eprintln!("warning: {}", problem);
+    }
+    println!("{}", man.stats());
+    Ok(())
+}
+
+#[cfg(feature = "export")]
+fn export(url: &str, format: &str, table: &str) -> Result<(), rman::Error> {
+    let mut agent = ureq::AgentBuilder::new().build();
+    let man = rman::Manifest::download(&mut agent, url)?;
+    let format = rman::ExportFormat::parse(format)?;
+    let table = rman::ExportTable::parse(table)?;
+    man.export(&mut std::io::stdout().lock(), format, table)
+}
+
+#[cfg(feature = "export")]
+fn import(input: &str, output: &str) -> Result<(), rman::Error> {
+    let mut reader = std::fs::File::open(input)
+        .map_err(|err| rman::Error::io("Failed to open json!", err).with_path(input))?;
+    let man = rman::Manifest::import(&mut std::io::BufReader::new(&mut reader))?;
+    let mut writer = std::fs::File::create(output)
+        .map_err(|err| rman::Error::io("Failed to create manifest!", err).with_path(output))?;
+    man.write(&mut writer)
+}
+
+fn update(
+    old_url: &str,
+    new_url: &str,
+    dir: &str,
+    cdn: &str,
+    langs: &str,
+    selector: rman::Selector,
+) -> Result<(), rman::Error> {
+    let mut agent = ureq::AgentBuilder::new().build();
+    let old = rman::Manifest::download(&mut agent, old_url)?;
+    let new = rman::Manifest::download(&mut agent, new_url)?;
+    let langs = new.parse_lang_filter(langs)?;
+    let plan = new.select(&selector.langs(langs)).plan_update(&old, dir);
+    println!(
+        "{} files to update, {} unchanged, {} bytes from local files, {} bytes to download",
+        plan.files.len(),
+        plan.unchanged.len(),
+        plan.get_local_size(),
+        plan.get_download_size()
+    );
+    plan.apply(dir, &mut agent, cdn)
+}
+
+fn run() -> Result<(), rman::Error> {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let mut args = args.iter().map(String::as_str).collect::<Vec<_>>();
+    let count = args.len();
+    let selector = parse_selector(&mut args)?;
+    let filtered = args.len() != count;
+    match args.as_slice() {
+        ["verify", url, dir] => verify(url, dir, "neutral", "", selector),
+        ["verify", url, dir, mode @ ("--fast" | "--force")] => {
+            verify(url, dir, "neutral", mode, selector)
+        }
+        ["verify", url, dir, langs] => verify(url, dir, langs, "", selector),
+        ["verify", url, dir, langs, mode @ ("--fast" | "--force")] => {
+            verify(url, dir, langs, mode, selector)
+        }
+        ["repair", url, dir, cdn] => repair(url, dir, cdn, "neutral", "", selector),
+        ["repair", url, dir, cdn, mode @ ("--fast" | "--force")] => {
+            repair(url, dir, cdn, "neutral", mode, selector)
+        }
+        ["repair", url, dir, cdn, langs] => repair(url, dir, cdn, langs, "", selector),
+        ["repair", url, dir, cdn, langs, mode @ ("--fast" | "--force")] => {
+            repair(url, dir, cdn, langs, mode, selector)
+        }
+        ["scan", url, dir] if !filtered => prune(url, dir, "neutral", true),
+        ["scan", url, dir, langs] if !filtered => prune(url, dir, langs, true),
+        ["prune", url, dir, langs] if !filtered => prune(url, dir, langs, false),
+        ["prune", url, dir, langs, "--dry-run"] if !filtered => prune(url, dir, langs, true),
+        ["download", url, dir, cdn] => download(url, dir, cdn, "neutral", "", selector),
+        ["download", url, dir, cdn, mode @ ("--fast" | "--force")] => {
+            download(url, dir, cdn, "neutral", mode, selector)
+        }
+        ["download", url, dir, cdn, langs] => download(url, dir, cdn, langs, "", selector),
+        ["download", url, dir, cdn, langs, mode @ ("--fast" | "--force")] => {
+            download(url, dir, cdn, langs, mode, selector)
+        }
+        ["diff", old_url, new_url] => diff(old_url, new_url),
+        ["stats", url] => stats(url),
+        ["find", url, path] => find(url, path),
+        ["roundtrip", url] => roundtrip(url),
+        ["inspect", url] => inspect(url, "tree"),
+        ["inspect", url, format @ ("tree" | "json")] => inspect(url, format),
+        #[cfg(feature = "export")]
+        ["export", url, format] => export(url, format, "manifest"),
+        #[cfg(feature = "export")]
+        ["export", url, format, table] => export(url, format, table),
+        #[cfg(feature = "export")]
+        ["import", input, output] => import(input, output),
+        ["update", old_url, new_url, dir, cdn] => {
+            update(old_url, new_url, dir, cdn, "neutral", selector)
+        }
+        ["update", old_url, new_url, dir, cdn, langs] => {
+            update(old_url, new_url, dir, cdn, langs, selector)
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("error: {}", err.chain());
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,176 @@
+use super::{Error, File, VerifyReport};
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, BufRead, Write},
+    time::UNIX_EPOCH,
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheEntry {
+    pub manifest_id: u64,
+    pub file_id: u64,
+    pub size: u64,
+    pub mtime: u64,
+    pub good: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct VerifyCache {
+    pub dir: String,
+    pub manifest_id: u64,
+    pub force: bool,
+    pub entries: HashMap<String, CacheEntry>,
+}
+
+fn get_mtime(metadata: &fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |time| time.as_nanos() as u64)
+}
+
+impl CacheEntry {
+    fn parse(line: &str) -> Option<(String, Self)> {
+        let mut parts = line.splitn(6, '\t');
+        let manifest_id = u64::from_str_radix(parts.next()?, 16).ok()?;
+        let file_id = u64::from_str_radix(parts.next()?, 16).ok()?;
+        let size = parts.next()?.parse().ok()?;
+        let mtime = parts.next()?.parse().ok()?;
+        let good = match parts.next()? {
+            "good" => true,
+            "bad" => false,
+            _ => return None,
+        };
+        let name = parts.next()?.to_string();
+        Some((
+            name,
+            Self {
+                manifest_id,
+                file_id,
+                size,
+                mtime,
+                good,
+            },
+        ))
+    }
+}
+
+impl VerifyCache {
+    pub const FILE_NAME: &'static str = ".rman-verify";
+    const HEADER: &'static str = "rman-verify 1";
+
+    pub fn new(dir: &str, manifest_id: u64) -> Self {
+        Self {
+            dir: dir.to_string(),
+            manifest_id,
+            force: false,
+            entries: HashMap::new(),
+        }
+    }
+
+    pub fn load(dir: &str, manifest_id: u64) -> Self {
+        let mut result = Self::new(dir, manifest_id);
+        let reader = match fs::File::open(result.get_path()) {
+            Ok(reader) => io::BufReader::new(reader),
+            Err(_) => return result,
+        };
+        let mut lines = reader.lines();
+        if !lines
+            .next()
+            .is_some_and(|header| header.is_ok_and(|header| header == Self::HEADER))
+        {
+            return result;
+        }
+        for line in lines.map_while(Result::ok) {
+            if let Some((name, entry)) = CacheEntry::parse(&line) {
+                result.entries.insert(name, entry);
+            }
+        }
+        result
+    }
+
+    pub fn force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    pub fn get_path(&self) -> String {
+        format!("{}/{}", self.dir, Self::FILE_NAME)
+    }
+
+    pub fn is_good(&self, file: &File, metadata: &fs::Metadata) -> bool {
+        !self.force
+            && self.entries.get(&file.name).is_some_and(|entry| {
+                entry.good
+                    && entry.manifest_id == self.manifest_id
+                    && entry.file_id == file.id
+                    && entry.size == file.size
+                    && entry.size == metadata.len()
+                    && entry.mtime == get_mtime(metadata)
+            })
+    }
+
+    pub fn is_good_in_dir(&self, file: &File) -> bool {
+        !file.is_link()
+            && fs::metadata(format!("{}/{}", self.dir, &file.name))
+                .is_ok_and(|metadata| self.is_good(file, &metadata) && file.has_mode(&metadata))
+    }
+
+    pub fn insert(&mut self, file: &File, metadata: &fs::Metadata, good: bool) {
+        self.entries.insert(
+            file.name.clone(),
+            CacheEntry {
+                manifest_id: self.manifest_id,
+                file_id: file.id,
+                size: metadata.len(),
+                mtime: get_mtime(metadata),
+                good,
+            },
+        );
+    }
+
+    pub fn update(&mut self, report: &VerifyReport) {
+        for result in &report.files {
+            match &result.metadata {
+                Some(metadata) => self.insert(result.file, metadata, result.is_ok()),
+                None => {
+                    self.entries.remove(&result.file.name);
+                }
+            }
+        }
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let path = self.get_path();
+        let temp = format!("{}.tmp", &path);
+        let mut names = self.entries.keys().collect::<Vec<_>>();
+        names.sort();
+        let mut writer = io::BufWriter::new(
+            fs::File::create(&temp)
+                .map_err(|err| Error::io("Failed to create cache!", err).with_path(&temp))?,
+        );
+        writeln!(writer, "{}", Self::HEADER)
+            .and_then(|_| {
+                names.iter().try_for_each(|&name| {
+                    let entry = &self.entries[name];
+                    writeln!(
+                        writer,
+                        "{:016X}\t{:016X}\t{}\t{}\t{}\t{}",
+                        entry.manifest_id,
+                        entry.file_id,
+                        entry.size,
+                        entry.mtime,
+                        if entry.good { "good" } else { "bad" },
+                        name
+                    )
+                })
+            })
+            .and_then(|_| writer.flush())
+            .map_err(|err| Error::io("Failed to write cache!", err).with_path(&temp))?;
+        drop(writer);
+        fs::rename(&temp, &path)
+            .map_err(|err| Error::io("Failed to replace cache!", err).with_path(&path))
+    }
+}
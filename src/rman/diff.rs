@@ -0,0 +1,274 @@
+use super::{File, Lang, Manifest};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt,
+};
+
+#[derive(Clone, Debug)]
+pub struct ModifiedFile<'a> {
+    pub old: &'a File,
+    pub new: &'a File,
+    pub new_chunks: usize,
+    pub reused_chunks: usize,
+    pub fetch_size: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct RenamedFile<'a> {
+    pub old: &'a File,
+    pub new: &'a File,
+}
+
+#[derive(Clone, Debug)]
+pub struct LangChange<'a> {
+    pub old: &'a File,
+    pub new: &'a File,
+    pub old_langs: Vec<&'a str>,
+    pub new_langs: Vec<&'a str>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ManifestDiff<'a> {
+    pub old: &'a Manifest,
+    pub new: &'a Manifest,
+    pub added: Vec<&'a File>,
+    pub removed: Vec<&'a File>,
+    pub modified: Vec<ModifiedFile<'a>>,
+    pub renamed: Vec<RenamedFile<'a>>,
+    pub lang_changes: Vec<LangChange<'a>>,
+    pub added_langs: Vec<&'a Lang>,
+    pub removed_langs: Vec<&'a Lang>,
+    pub new_chunks: usize,
+    pub reused_chunks: usize,
+    pub fetch_size: u64,
+}
+
+fn get_lang_names<'a>(manifest: &'a Manifest, file: &File) -> Vec<&'a str> {
+    let mut names = manifest.get_lang_names(file.langs);
+    names.sort_by_key(|name| name.to_lowercase());
+    names
+}
+
+fn has_same_langs(old: &[&str], new: &[&str]) -> bool {
+    old.len() == new.len()
+        && old
+            .iter()
+            .zip(new.iter())
+            .all(|(old, new)| old.eq_ignore_ascii_case(new))
+}
+
+fn has_same_chunks(old: &File, new: &File) -> bool {
+    old.size == new.size
+        && old.chunks.len() == new.chunks.len()
+        && old
+            .chunks
+            .iter()
+            .zip(new.chunks.iter())
+            .all(|(old, new)| old.chunk_id == new.chunk_id)
+}
+
+impl<'a> ManifestDiff<'a> {
+    pub fn new(old: &'a Manifest, new: &'a Manifest) -> Self {
+        let old_files = old
+            .files
+            .iter()
+            .map(|file| (file.name.as_str(), file))
+            .collect::<HashMap<_, _>>();
+        let new_files = new
+            .files
+            .iter()
+            .map(|file| (file.name.as_str(), file))
+            .collect::<HashMap<_, _>>();
+        let old_chunks = old
+            .files
+            .iter()
+            .flat_map(|file| file.chunks.iter().map(|chunk| chunk.chunk_id))
+            .collect::<HashSet<_>>();
+        let mut result = Self {
+            old,
+            new,
+            added: Vec::new(),
+            removed: Vec::new(),
+            modified: Vec::new(),
+            renamed: Vec::new(),
+            lang_changes: Vec::new(),
+            added_langs: new
+                .langs
+                .iter()
+                .filter(|lang| old.get_lang_by_name(&lang.name).is_none())
+                .collect(),
+            removed_langs: old
+                .langs
+                .iter()
+                .filter(|lang| new.get_lang_by_name(&lang.name).is_none())
+                .collect(),
+            new_chunks: 0,
+            reused_chunks: 0,
+            fetch_size: 0,
+        };
+        let mut added = Vec::new();
+        for file in &new.files {
+            match old_files.get(file.name.as_str()) {
+                Some(&old_file) => {
+                    result.compare(old_file, file);
+                    if !has_same_chunks(old_file, file) || old_file.link_name != file.link_name {
+                        result
+                            .modified
+                            .push(Self::modify(old_file, file, &old_chunks));
+                    }
+                }
+                None => added.push(file),
+            }
+        }
+        let mut removed = old
+            .files
+            .iter()
+            .filter(|file| !new_files.contains_key(file.name.as_str()))
+            .collect::<Vec<_>>();
+        let mut removed_by_chunks = HashMap::<Vec<u64>, Vec<&File>>::new();
+        for &file in removed.iter().rev() {
+            if !file.chunks.is_empty() {
+                removed_by_chunks
+                    .entry(file.chunks.iter().map(|chunk| chunk.chunk_id).collect())
+                    .or_default()
+                    .push(file);
+            }
+        }
+        let mut renamed_old = HashSet::new();
+        for file in added {
+            let chunk_ids = file
+                .chunks
+                .iter()
+                .map(|chunk| chunk.chunk_id)
+                .collect::<Vec<_>>();
+            match removed_by_chunks
+                .get_mut(&chunk_ids)
+                .and_then(|files| files.pop())
+            {
+                Some(old_file) if has_same_chunks(old_file, file) => {
+                    renamed_old.insert(old_file.name.as_str());
+                    result.compare(old_file, file);
+                    result.renamed.push(RenamedFile {
+                        old: old_file,
+                        new: file,
+                    });
+                }
+                _ => result.added.push(file),
+            }
+        }
+        removed.retain(|file| !renamed_old.contains(file.name.as_str()));
+        result.removed = removed;
+        let mut seen = HashSet::new();
+        let fetched = result
+            .added
+            .iter()
+            .copied()
+            .chain(result.modified.iter().map(|modified| modified.new))
+            .flat_map(|file| file.chunks.iter());
+        for chunk in fetched {
+            if !seen.insert(chunk.chunk_id) {
+                continue;
+            }
+            if old_chunks.contains(&chunk.chunk_id) {
+                result.reused_chunks += 1;
+            } else {
+                result.new_chunks += 1;
+                result.fetch_size += chunk.size_compressed as u64;
+            }
+        }
+        result
+    }
+
+    fn compare(&mut self, old: &'a File, new: &'a File) {
+        let old_langs = get_lang_names(self.old, old);
+        let new_langs = get_lang_names(self.new, new);
+        if !has_same_langs(&old_langs, &new_langs) {
+            self.lang_changes.push(LangChange {
+                old,
+                new,
+                old_langs,
+                new_langs,
+            });
+        }
+    }
+
+    fn modify(old: &'a File, new: &'a File, old_chunks: &HashSet<u64>) -> ModifiedFile<'a> {
+        let mut result = ModifiedFile {
+            old,
+            new,
+            new_chunks: 0,
+            reused_chunks: 0,
+            fetch_size: 0,
+        };
+        let mut seen = BTreeSet::new();
+        for chunk in &new.chunks {
+            if !seen.insert(chunk.chunk_id) {
+                continue;
+            }
+            if old_chunks.contains(&chunk.chunk_id) {
+                result.reused_chunks += 1;
+            } else {
+                result.new_chunks += 1;
+                result.fetch_size += chunk.size_compressed as u64;
+            }
+        }
+        result
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.modified.is_empty()
+            && self.renamed.is_empty()
+            && self.lang_changes.is_empty()
+            && self.added_langs.is_empty()
+            && self.removed_langs.is_empty()
+    }
+}
+
+impl<'a> fmt::Display for ManifestDiff<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for lang in &self.added_langs {
+            writeln!(f, "+lang {}", lang)?;
+        }
+        for lang in &self.removed_langs {
+            writeln!(f, "-lang {}", lang)?;
+        }
+        for file in &self.added {
+            writeln!(f, "A {}", file.name)?;
+        }
+        for file in &self.removed {
+            writeln!(f, "D {}", file.name)?;
+        }
+        for modified in &self.modified {
+            writeln!(
+                f,
+                "M {} ({} new, {} reused chunks, {} bytes to fetch)",
+                modified.new.name, modified.new_chunks, modified.reused_chunks, modified.fetch_size
+            )?;
+        }
+        for renamed in &self.renamed {
+            writeln!(f, "R {} -> {}", renamed.old.name, renamed.new.name)?;
+        }
+        for change in &self.lang_changes {
+            writeln!(
+                f,
+                "L {} [{}] -> [{}]",
+                change.new.name,
+                change.old_langs.join(", "),
+                change.new_langs.join(", ")
+            )?;
+        }
+        write!(
+            f,
+            "{} added, {} removed, {} modified, {} renamed, {} new chunks, {} reused chunks, {} bytes to fetch",
+            self.added.len(),
+            self.removed.len(),
+            self.modified.len(),
+            self.renamed.len(),
+            self.new_chunks,
+            self.reused_chunks,
+            self.fetch_size
+        )
+    }
+}
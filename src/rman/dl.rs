@@ -1,61 +1,108 @@
-use super::{re_throw, throw};
+use super::{resolve_link, throw, Error};
 use std::{
     collections::{BTreeMap, BTreeSet, HashMap},
     fs,
-    io::{self, Read},
+    io::{self, Read, Seek, Write},
     ops::Range,
+    path::{Path, PathBuf},
 };
-use ureq;
-use zstd;
 
 #[derive(Clone, Debug, Default)]
 pub struct DownloadChunk {
     pub size_compressed: u32,
     pub size_uncompressed: u32,
-    pub offset_uncompressed: BTreeSet<u32>,
+    pub offset_uncompressed: BTreeSet<u64>,
 }
 
 #[derive(Clone, Debug, Default)]
 pub struct DownloadBundle {
     pub name: String,
-    pub offset_compressed: BTreeMap<u32, DownloadChunk>,
+    pub offset_compressed: BTreeMap<u64, DownloadChunk>,
 }
 
 #[derive(Clone, Debug, Default)]
 pub struct DownloadFile {
     pub name: String,
-    pub size: u32,
+    pub link_name: String,
+    pub mode: u32,
+    pub size: u64,
     pub max_uncompressed: u32,
     pub bundles: HashMap<u64, DownloadBundle>,
 }
 
+#[derive(Clone, Debug, Default)]
+pub struct PlanChunk {
+    pub size_compressed: u32,
+    pub size_uncompressed: u32,
+    pub targets: BTreeSet<(usize, u64)>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct PlanBundle {
+    pub name: String,
+    pub offset_compressed: BTreeMap<u64, PlanChunk>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct DownloadPlan {
+    pub files: Vec<DownloadFile>,
+    pub bundles: BTreeMap<u64, PlanBundle>,
+}
+
+struct PlanWriter<'a> {
+    dir: &'a str,
+    files: &'a [DownloadFile],
+    open: HashMap<usize, fs::File>,
+}
+
+fn decompress_chunk(src: &[u8], size_compressed: u32) -> Result<Vec<u8>, Error> {
+    if src.len() < size_compressed as usize {
+        return throw(Error::integrity("Chunk compressed data too small!"));
+    }
+    zstd::decode_all(&src[..size_compressed as usize])
+        .map_err(|err| Error::decompression("Failed to decompress chunk!", err))
+}
+
+fn fetch_range(agent: &mut ureq::Agent, url: &str, range: &Range<u64>) -> Result<Vec<u8>, Error> {
+    let response = agent
+        .get(url)
+        .set("Range", &format!("bytes={}-{}", range.start, range.end - 1))
+        .call()
+        .map_err(|err| Error::network("Failed to download!", err).with_path(url))?;
+    let mut buffer = vec![0; (range.end - range.start) as usize];
+    response
+        .into_reader()
+        .read_exact(&mut buffer)
+        .map_err(|err| Error::io("Failed to read response!", err).with_path(url))?;
+    Ok(buffer)
+}
+
 impl DownloadChunk {
     pub fn write_from<W: io::Write + io::Seek>(
         &self,
         src: &[u8],
         writer: &mut W,
-    ) -> Result<(), String> {
-        if src.len() < self.size_compressed as usize {
-            return throw("Chunk compressed data too small!");
-        }
-        let compressed = &src[..self.size_compressed as usize];
-        let uncompressed = re_throw(zstd::decode_all(compressed), "Failed to decompress chunk!")?;
+    ) -> Result<(), Error> {
+        let uncompressed = decompress_chunk(src, self.size_compressed)?;
         for &offset_uncompressed in &self.offset_uncompressed {
-            re_throw(
-                writer.seek(io::SeekFrom::Start(offset_uncompressed as u64)),
-                "Failed to seek to chunk!",
-            )?;
-            re_throw(writer.write_all(&uncompressed), "Failed to write chunk!")?;
+            writer
+                .seek(io::SeekFrom::Start(offset_uncompressed))
+                .map_err(|err| {
+                    Error::io("Failed to seek to chunk!", err).with_offset(offset_uncompressed)
+                })?;
+            writer.write_all(&uncompressed).map_err(|err| {
+                Error::io("Failed to write chunk!", err).with_offset(offset_uncompressed)
+            })?;
         }
         Ok(())
     }
 }
 
 impl DownloadBundle {
-    pub fn get_range(&self) -> Range<u32> {
+    pub fn get_range(&self) -> Range<u64> {
         if let Some((first_offset, _)) = self.offset_compressed.first_key_value() {
             if let Some((last_offset, last_chunk)) = self.offset_compressed.last_key_value() {
-                return first_offset + 0..last_offset + last_chunk.size_compressed;
+                return *first_offset..last_offset + last_chunk.size_compressed as u64;
             }
         }
         0..0
@@ -66,46 +113,131 @@ impl DownloadBundle {
         agent: &mut ureq::Agent,
         cdn: &str,
         writer: &mut W,
-    ) -> Result<u32, String> {
+    ) -> Result<u64, Error> {
         let range = self.get_range();
-        let response = re_throw(
-            agent
-                .get(&format!("{}/{}", cdn, self.name))
-                .set("Range", &format!("bytes={}-{}", range.start, range.end - 1))
-                .call(),
-            "Failed to download!",
-        )?;
-        let mut buffer = Vec::with_capacity(range.len());
-        buffer.resize(range.len(), 0u8);
-        re_throw(
-            response.into_reader().read_exact(&mut buffer),
-            "Failed to read response!",
-        )?;
+        let size = range.end - range.start;
+        let buffer = fetch_range(agent, &format!("{}/{}", cdn, self.name), &range)?;
         for (&offset_compressed, chunk) in &self.offset_compressed {
             let compressed = &buffer[(offset_compressed - range.start) as usize..];
-            chunk.write_from(compressed, writer)?;
+            chunk
+                .write_from(compressed, writer)
+                .map_err(|err| err.with_offset(offset_compressed))?;
+        }
+        Ok(size)
+    }
+}
+
+fn create_link(target: &str, path: &Path) -> io::Result<()> {
+    if fs::symlink_metadata(path).is_ok() {
+        fs::remove_file(path)?;
+    }
+    #[cfg(unix)]
+    return std::os::unix::fs::symlink(target, path);
+    #[cfg(windows)]
+    return std::os::windows::fs::symlink_file(target, path);
+    #[cfg(not(any(unix, windows)))]
+    return Err(io::Error::new(
+        io::ErrorKind::Other,
+        "Symlinks are not supported!",
+    ));
+}
+
+fn get_path_in_dir(dir: &str, name: &str) -> Result<PathBuf, Error> {
+    let outside = || Error::validation("File path goes outside the root!").with_path(name);
+    let canonicalize = |path: &Path| {
+        fs::canonicalize(path)
+            .map_err(|err| Error::io("Failed to resolve path!", err).with_path(name))
+    };
+    fs::create_dir_all(dir)
+        .map_err(|err| Error::io("Failed to create root dir!", err).with_path(dir))?;
+    let root = canonicalize(Path::new(dir))?;
+    let path = root.join(name);
+    let (parent, file_name) = match (path.parent(), path.file_name()) {
+        (Some(parent), Some(file_name)) => (parent, file_name),
+        _ => return throw(outside()),
+    };
+    if let Some(existing) = parent.ancestors().find(|path| path.exists()) {
+        if !canonicalize(existing)?.starts_with(&root) {
+            return throw(outside());
         }
-        Ok(range.len() as u32)
     }
+    fs::create_dir_all(parent)
+        .map_err(|err| Error::io("Failed to create file dirs!", err).with_path(name))?;
+    let parent = canonicalize(parent)?;
+    if !parent.starts_with(&root) {
+        return throw(outside());
+    }
+    Ok(parent.join(file_name))
 }
 
 impl DownloadFile {
-    pub fn get_total_size(&self) -> u32 {
+    pub fn is_link(&self) -> bool {
+        !self.link_name.is_empty()
+    }
+
+    #[cfg(unix)]
+    pub fn set_mode(&self, file: &fs::File) -> io::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(self.mode))
+    }
+
+    #[cfg(not(unix))]
+    pub fn set_mode(&self, _file: &fs::File) -> io::Result<()> {
+        Ok(())
+    }
+
+    pub fn download_link_in_dir(&self, dir: &str) -> Result<(), Error> {
+        resolve_link(&self.name, &self.link_name)?;
+        let path = get_path_in_dir(dir, &self.name)?;
+        create_link(&self.link_name, &path)
+            .map_err(|err| Error::io("Failed to create link!", err).with_path(&self.name))
+    }
+
+    pub fn open_in_dir(&self, dir: &str) -> Result<fs::File, Error> {
+        let path = get_path_in_dir(dir, &self.name)?;
+        if fs::symlink_metadata(&path).is_ok_and(|metadata| metadata.file_type().is_symlink()) {
+            fs::remove_file(&path)
+                .map_err(|err| Error::io("Failed to remove link!", err).with_path(&self.name))?;
+        }
+        fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .map_err(|err| Error::io("Failed to open file!", err).with_path(&self.name))
+    }
+
+    pub fn finish_in_dir(&self, dir: &str) -> Result<(), Error> {
+        let path = format!("{}/{}", dir, &self.name);
+        let writer = self.open_in_dir(dir)?;
+        writer
+            .set_len(self.size)
+            .map_err(|err| Error::io("Failed to set file len!", err).with_path(&path))?;
+        self.set_mode(&writer)
+            .map_err(|err| Error::io("Failed to set file mode!", err).with_path(&path))
+    }
+
+    pub fn get_total_size(&self) -> u64 {
         self.bundles
             .values()
-            .map(|bundle| bundle.get_range().len())
-            .sum::<usize>() as u32
+            .map(|bundle| {
+                let range = bundle.get_range();
+                range.end - range.start
+            })
+            .sum()
     }
 
-    pub fn download_with_progress<W: io::Write + io::Seek, F: FnMut(u32)>(
+    pub fn download_with_progress<W: io::Write + io::Seek, F: FnMut(u64)>(
         &self,
         agent: &mut ureq::Agent,
         cdn: &str,
         writer: &mut W,
         mut progress: F,
-    ) -> Result<(), String> {
-        for (_, bundle) in &self.bundles {
-            let done_count = bundle.download(agent, cdn, writer)?;
+    ) -> Result<(), Error> {
+        for (&bundle_id, bundle) in &self.bundles {
+            let done_count = bundle
+                .download(agent, cdn, writer)
+                .map_err(|err| err.with_bundle(bundle_id))?;
             progress(done_count);
         }
         Ok(())
@@ -116,24 +248,174 @@ impl DownloadFile {
         agent: &mut ureq::Agent,
         cdn: &str,
         writer: &mut W,
-    ) -> Result<(), String> {
+    ) -> Result<(), Error> {
         self.download_with_progress(agent, cdn, writer, |_| ())
     }
 
-    pub fn download_in_dir_with_progress<F: FnMut(u32)>(
+    pub fn download_in_dir_with_progress<F: FnMut(u64)>(
         &self,
         dir: &str,
         agent: &mut ureq::Agent,
         cdn: &str,
-        mut progress: F,
-    ) -> Result<(), String> {
+        progress: F,
+    ) -> Result<(), Error> {
+        if self.is_link() {
+            return self.download_link_in_dir(dir);
+        }
         let path = format!("{}/{}", dir, &self.name);
-        if let Some(parent) = std::path::Path::new(&path).parent() {
-            re_throw(fs::create_dir_all(parent), "Failed to create file dirs!")?;
+        let mut writer = self.open_in_dir(dir)?;
+        self.download_with_progress(agent, cdn, &mut writer, progress)
+            .map_err(|err| err.with_path(&path))?;
+        writer
+            .set_len(self.size)
+            .map_err(|err| Error::io("Failed to set file len!", err).with_path(&path))?;
+        self.set_mode(&writer)
+            .map_err(|err| Error::io("Failed to set file mode!", err).with_path(&path))?;
+        Ok(())
+    }
+
+    pub fn download_in_dir(
+        &self,
+        dir: &str,
+        agent: &mut ureq::Agent,
+        cdn: &str,
+    ) -> Result<(), Error> {
+        self.download_in_dir_with_progress(dir, agent, cdn, |_| ())
+    }
+}
+
+impl<'a> PlanWriter<'a> {
+    const MAX_OPEN: usize = 64;
+
+    fn get(&mut self, index: usize) -> Result<&mut fs::File, Error> {
+        if !self.open.contains_key(&index) {
+            if self.open.len() >= Self::MAX_OPEN {
+                self.open.clear();
+            }
+            let file = self.files[index].open_in_dir(self.dir)?;
+            self.open.insert(index, file);
+        }
+        Ok(self.open.get_mut(&index).unwrap())
+    }
+
+    fn write(&mut self, index: usize, offset: u64, data: &[u8]) -> Result<(), Error> {
+        let name = &self.files[index].name;
+        self.get(index)?
+            .seek(io::SeekFrom::Start(offset))
+            .and_then(|_| self.open.get_mut(&index).unwrap().write_all(data))
+            .map_err(|err| {
+                Error::io("Failed to write chunk!", err)
+                    .with_path(name)
+                    .with_offset(offset)
+            })
+    }
+}
+
+impl PlanBundle {
+    pub const MAX_GAP: u64 = 64 * 1024;
+
+    pub fn get_ranges(&self) -> Vec<Range<u64>> {
+        let mut ranges = Vec::<Range<u64>>::new();
+        for (&offset, chunk) in &self.offset_compressed {
+            let end = offset + chunk.size_compressed as u64;
+            match ranges.last_mut() {
+                Some(range) if offset <= range.end + Self::MAX_GAP => {
+                    range.end = range.end.max(end)
+                }
+                _ => ranges.push(offset..end),
+            }
+        }
+        ranges
+    }
+
+    pub fn get_total_size(&self) -> u64 {
+        self.get_ranges()
+            .iter()
+            .map(|range| range.end - range.start)
+            .sum()
+    }
+}
+
+impl DownloadPlan {
+    pub fn new(files: Vec<DownloadFile>) -> Self {
+        let mut bundles = BTreeMap::<u64, PlanBundle>::new();
+        for (index, file) in files.iter().enumerate() {
+            for (&bundle_id, bundle) in &file.bundles {
+                let plan_bundle = bundles.entry(bundle_id).or_insert_with(|| PlanBundle {
+                    name: bundle.name.clone(),
+                    offset_compressed: BTreeMap::new(),
+                });
+                for (&offset_compressed, chunk) in &bundle.offset_compressed {
+                    plan_bundle
+                        .offset_compressed
+                        .entry(offset_compressed)
+                        .or_insert_with(|| PlanChunk {
+                            size_compressed: chunk.size_compressed,
+                            size_uncompressed: chunk.size_uncompressed,
+                            targets: BTreeSet::new(),
+                        })
+                        .targets
+                        .extend(
+                            chunk
+                                .offset_uncompressed
+                                .iter()
+                                .map(|&offset| (index, offset)),
+                        );
+                }
+            }
+        }
+        Self { files, bundles }
+    }
+
+    pub fn get_chunk_count(&self) -> usize {
+        self.bundles
+            .values()
+            .map(|bundle| bundle.offset_compressed.len())
+            .sum()
+    }
+
+    pub fn get_total_size(&self) -> u64 {
+        self.bundles.values().map(PlanBundle::get_total_size).sum()
+    }
+
+    pub fn download_in_dir_with_progress<F: FnMut(u64)>(
+        &self,
+        dir: &str,
+        agent: &mut ureq::Agent,
+        cdn: &str,
+        mut progress: F,
+    ) -> Result<(), Error> {
+        for file in self.files.iter().filter(|file| file.is_link()) {
+            file.download_link_in_dir(dir)?;
+        }
+        let mut writer = PlanWriter {
+            dir,
+            files: &self.files,
+            open: HashMap::new(),
+        };
+        for index in (0..self.files.len()).filter(|&index| !self.files[index].is_link()) {
+            writer.get(index)?;
+        }
+        for (&bundle_id, bundle) in &self.bundles {
+            let url = format!("{}/{}", cdn, bundle.name);
+            for range in bundle.get_ranges() {
+                let buffer =
+                    fetch_range(agent, &url, &range).map_err(|err| err.with_bundle(bundle_id))?;
+                for (&offset_compressed, chunk) in bundle.offset_compressed.range(range.clone()) {
+                    let src = &buffer[(offset_compressed - range.start) as usize..];
+                    let uncompressed = decompress_chunk(src, chunk.size_compressed)
+                        .map_err(|err| err.with_bundle(bundle_id).with_offset(offset_compressed))?;
+                    for &(index, offset) in &chunk.targets {
+                        writer.write(index, offset, &uncompressed)?;
+                    }
+                }
+                progress(range.end - range.start);
+            }
+        }
+        drop(writer);
+        for file in self.files.iter().filter(|file| !file.is_link()) {
+            file.finish_in_dir(dir)?;
         }
-        let mut writer = re_throw(fs::File::create(&path), "Failed to create file!")?;
-        self.download_with_progress(agent, cdn, &mut writer, progress)?;
-        re_throw(writer.set_len(self.size as u64), "Failed to set file len!")?;
         Ok(())
     }
 
@@ -142,7 +424,7 @@ impl DownloadFile {
         dir: &str,
         agent: &mut ureq::Agent,
         cdn: &str,
-    ) -> Result<(), String> {
+    ) -> Result<(), Error> {
         self.download_in_dir_with_progress(dir, agent, cdn, |_| ())
     }
 }
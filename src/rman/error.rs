@@ -0,0 +1,217 @@
+use std::{fmt, io};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Context {
+    pub path: Option<String>,
+    pub bundle_id: Option<u64>,
+    pub chunk_id: Option<u64>,
+    pub offset: Option<u64>,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Parse {
+        message: String,
+        context: Context,
+    },
+    Network {
+        message: String,
+        context: Context,
+        source: Box<ureq::Error>,
+    },
+    Io {
+        message: String,
+        context: Context,
+        source: io::Error,
+    },
+    Compression {
+        message: String,
+        context: Context,
+        source: io::Error,
+    },
+    Decompression {
+        message: String,
+        context: Context,
+        source: io::Error,
+    },
+    Integrity {
+        message: String,
+        context: Context,
+    },
+    Validation {
+        message: String,
+        context: Context,
+    },
+}
+
+impl Context {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl fmt::Display for Context {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(path) = &self.path {
+            parts.push(format!("path {}", path));
+        }
+        if let Some(bundle_id) = self.bundle_id {
+            parts.push(format!("bundle {:016X}", bundle_id));
+        }
+        if let Some(chunk_id) = self.chunk_id {
+            parts.push(format!("chunk {:016X}", chunk_id));
+        }
+        if let Some(offset) = self.offset {
+            parts.push(format!("offset {}", offset));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+impl Error {
+    pub fn parse<S: ToString>(message: S) -> Self {
+        Self::Parse {
+            message: message.to_string(),
+            context: Context::default(),
+        }
+    }
+
+    pub fn network<S: ToString>(message: S, source: ureq::Error) -> Self {
+        Self::Network {
+            message: message.to_string(),
+            context: Context::default(),
+            source: Box::new(source),
+        }
+    }
+
+    pub fn io<S: ToString>(message: S, source: io::Error) -> Self {
+        Self::Io {
+            message: message.to_string(),
+            context: Context::default(),
+            source,
+        }
+    }
+
+    pub fn compression<S: ToString>(message: S, source: io::Error) -> Self {
+        Self::Compression {
+            message: message.to_string(),
+            context: Context::default(),
+            source,
+        }
+    }
+
+    pub fn decompression<S: ToString>(message: S, source: io::Error) -> Self {
+        Self::Decompression {
+            message: message.to_string(),
+            context: Context::default(),
+            source,
+        }
+    }
+
+    pub fn integrity<S: ToString>(message: S) -> Self {
+        Self::Integrity {
+            message: message.to_string(),
+            context: Context::default(),
+        }
+    }
+
+    pub fn validation<S: ToString>(message: S) -> Self {
+        Self::Validation {
+            message: message.to_string(),
+            context: Context::default(),
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            Self::Parse { message, .. }
+            | Self::Network { message, .. }
+            | Self::Io { message, .. }
+            | Self::Compression { message, .. }
+            | Self::Decompression { message, .. }
+            | Self::Integrity { message, .. }
+            | Self::Validation { message, .. } => message,
+        }
+    }
+
+    pub fn context(&self) -> &Context {
+        match self {
+            Self::Parse { context, .. }
+            | Self::Network { context, .. }
+            | Self::Io { context, .. }
+            | Self::Compression { context, .. }
+            | Self::Decompression { context, .. }
+            | Self::Integrity { context, .. }
+            | Self::Validation { context, .. } => context,
+        }
+    }
+
+    fn context_mut(&mut self) -> &mut Context {
+        match self {
+            Self::Parse { context, .. }
+            | Self::Network { context, .. }
+            | Self::Io { context, .. }
+            | Self::Compression { context, .. }
+            | Self::Decompression { context, .. }
+            | Self::Integrity { context, .. }
+            | Self::Validation { context, .. } => context,
+        }
+    }
+
+    pub fn with_path<S: ToString>(mut self, path: S) -> Self {
+        let context = self.context_mut();
+        if context.path.is_none() {
+            context.path = Some(path.to_string());
+        }
+        self
+    }
+
+    pub fn with_bundle(mut self, bundle_id: u64) -> Self {
+        self.context_mut().bundle_id.get_or_insert(bundle_id);
+        self
+    }
+
+    pub fn with_chunk(mut self, chunk_id: u64) -> Self {
+        self.context_mut().chunk_id.get_or_insert(chunk_id);
+        self
+    }
+
+    pub fn with_offset(mut self, offset: u64) -> Self {
+        self.context_mut().offset.get_or_insert(offset);
+        self
+    }
+
+    pub fn chain(&self) -> String {
+        let mut result = self.to_string();
+        let mut source = std::error::Error::source(self);
+        while let Some(error) = source {
+            result = format!("{}: {}", result, error);
+            source = error.source();
+        }
+        result
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let context = self.context();
+        if context.is_empty() {
+            write!(f, "{}", self.message())
+        } else {
+            write!(f, "{} ({})", self.message(), context)
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Network { source, .. } => Some(source.as_ref()),
+            Self::Io { source, .. }
+            | Self::Compression { source, .. }
+            | Self::Decompression { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,633 @@
+use super::{
+    get_dir_paths,
+    raw::{self, verify_filename},
+    throw, Chunk, Dir, Error, File, FileMeta, HashType, Integrity, Lang, LangSet, Manifest, Params,
+};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt, io,
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct HexId(pub u64);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    Json,
+    Ndjson,
+    Csv,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportTable {
+    Manifest,
+    Files,
+    Chunks,
+    Bundles,
+    FileChunks,
+    Langs,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LangRecord {
+    pub id: u8,
+    pub name: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkRecord {
+    pub id: HexId,
+    pub size_compressed: u32,
+    pub size_uncompressed: u32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleRecord {
+    pub id: HexId,
+    pub chunks: Vec<ChunkRecord>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileRecord {
+    pub id: HexId,
+    pub name: String,
+    #[serde(default)]
+    pub link: String,
+    pub size: u64,
+    pub hash_type: String,
+    pub max_uncompressed: u32,
+    #[serde(default)]
+    pub permissions: u8,
+    #[serde(default)]
+    pub langs: Vec<String>,
+    #[serde(default)]
+    pub chunks: Vec<HexId>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestRecord {
+    pub id: HexId,
+    #[serde(default)]
+    pub langs: Vec<LangRecord>,
+    #[serde(default)]
+    pub bundles: Vec<BundleRecord>,
+    #[serde(default)]
+    pub dirs: Vec<String>,
+    #[serde(default)]
+    pub files: Vec<FileRecord>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileRow {
+    pub id: HexId,
+    pub name: String,
+    pub link: String,
+    pub size: u64,
+    pub hash_type: String,
+    pub max_uncompressed: u32,
+    pub permissions: u8,
+    pub langs: String,
+    pub chunk_count: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkRow {
+    pub id: HexId,
+    pub bundle_id: HexId,
+    pub offset_compressed: u64,
+    pub size_compressed: u32,
+    pub size_uncompressed: u32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleRow {
+    pub id: HexId,
+    pub chunk_count: usize,
+    pub size_compressed: u64,
+    pub size_uncompressed: u64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileChunkRow {
+    pub file_id: HexId,
+    pub chunk_id: HexId,
+    pub offset_uncompressed: u64,
+    pub size_uncompressed: u32,
+}
+
+impl Serialize for HexId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for HexId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = HexId;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a hex string or an integer id")
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<HexId, E> {
+                Ok(HexId(value))
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<HexId, E> {
+                u64::from_str_radix(value, 16)
+                    .map(HexId)
+                    .map_err(|_| E::custom(format!("bad hex id {:?}", value)))
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+impl fmt::Display for HexId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016X}", self.0)
+    }
+}
+
+impl ExportFormat {
+    pub fn parse(name: &str) -> Result<Self, Error> {
+        match name.to_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "ndjson" | "jsonl" => Ok(Self::Ndjson),
+            "csv" => Ok(Self::Csv),
+            _ => throw(Error::validation(format!(
+                "Unknown export format {}!",
+                name
+            ))),
+        }
+    }
+}
+
+impl ExportTable {
+    pub fn parse(name: &str) -> Result<Self, Error> {
+        match name.to_lowercase().as_str() {
+            "manifest" => Ok(Self::Manifest),
+            "files" => Ok(Self::Files),
+            "chunks" => Ok(Self::Chunks),
+            "bundles" => Ok(Self::Bundles),
+            "file_chunks" => Ok(Self::FileChunks),
+            "langs" => Ok(Self::Langs),
+            _ => throw(Error::validation(format!("Unknown export table {}!", name))),
+        }
+    }
+}
+
+fn write_rows<W: io::Write, T: Serialize>(
+    writer: &mut W,
+    format: ExportFormat,
+    rows: &[T],
+) -> Result<(), Error> {
+    let result = match format {
+        ExportFormat::Json => serde_json::to_writer_pretty(&mut *writer, rows)
+            .map_err(io::Error::from)
+            .and_then(|_| writeln!(writer)),
+        ExportFormat::Ndjson => rows.iter().try_for_each(|row| {
+            serde_json::to_writer(&mut *writer, row)
+                .map_err(io::Error::from)
+                .and_then(|_| writeln!(writer))
+        }),
+        ExportFormat::Csv => {
+            let mut csv_writer = csv::Writer::from_writer(&mut *writer);
+            rows.iter()
+                .try_for_each(|row| csv_writer.serialize(row))
+                .and_then(|_| csv_writer.flush().map_err(csv::Error::from))
+                .map_err(io::Error::from)
+        }
+    };
+    result.map_err(|err| Error::io("Failed to write export!", err))
+}
+
+impl File {
+    fn get_lang_record_names(&self, manifest: &Manifest) -> Vec<String> {
+        if self.langs.is_neutral() {
+            Vec::new()
+        } else {
+            manifest
+                .get_lang_names(self.langs)
+                .into_iter()
+                .map(String::from)
+                .collect()
+        }
+    }
+
+    pub fn to_record(&self, manifest: &Manifest) -> FileRecord {
+        FileRecord {
+            id: HexId(self.id),
+            name: self.name.clone(),
+            link: self.link_name.clone(),
+            size: self.size,
+            hash_type: self.hash_type.name().to_string(),
+            max_uncompressed: self.max_uncompressed,
+            permissions: self.meta.permissions,
+            langs: self.get_lang_record_names(manifest),
+            chunks: self
+                .chunks
+                .iter()
+                .map(|chunk| HexId(chunk.chunk_id))
+                .collect(),
+        }
+    }
+
+    pub fn to_row(&self, manifest: &Manifest) -> FileRow {
+        FileRow {
+            id: HexId(self.id),
+            name: self.name.clone(),
+            link: self.link_name.clone(),
+            size: self.size,
+            hash_type: self.hash_type.name().to_string(),
+            max_uncompressed: self.max_uncompressed,
+            permissions: self.meta.permissions,
+            langs: self.get_lang_record_names(manifest).join("+"),
+            chunk_count: self.chunks.len(),
+        }
+    }
+}
+
+impl Manifest {
+    fn get_chunk_rows(&self) -> Vec<ChunkRow> {
+        let mut rows = self
+            .chunks
+            .values()
+            .map(|chunk| ChunkRow {
+                id: HexId(chunk.chunk_id),
+                bundle_id: HexId(chunk.bundle_id),
+                offset_compressed: chunk.offset_compressed,
+                size_compressed: chunk.size_compressed,
+                size_uncompressed: chunk.size_uncompressed,
+            })
+            .collect::<Vec<_>>();
+        rows.sort_by_key(|row| (row.bundle_id, row.offset_compressed));
+        rows
+    }
+
+    fn get_bundle_rows(&self) -> Vec<BundleRow> {
+        let mut bundles = BTreeMap::<u64, BundleRow>::new();
+        for chunk in self.chunks.values() {
+            let row = bundles.entry(chunk.bundle_id).or_insert_with(|| BundleRow {
+                id: HexId(chunk.bundle_id),
+                ..Default::default()
+            });
+            row.chunk_count += 1;
+            row.size_compressed += chunk.size_compressed as u64;
+            row.size_uncompressed += chunk.size_uncompressed as u64;
+        }
+        bundles.into_values().collect()
+    }
+
+    fn get_file_chunk_rows(&self) -> Vec<FileChunkRow> {
+        self.files
+            .iter()
+            .flat_map(|file| {
+                file.chunks.iter().map(move |chunk| FileChunkRow {
+                    file_id: HexId(file.id),
+                    chunk_id: HexId(chunk.chunk_id),
+                    offset_uncompressed: chunk.offset_uncompressed,
+                    size_uncompressed: chunk.size_uncompressed,
+                })
+            })
+            .collect()
+    }
+
+    fn get_lang_records(&self) -> Vec<LangRecord> {
+        self.langs
+            .iter()
+            .map(|lang| LangRecord {
+                id: lang.id,
+                name: lang.name.clone(),
+            })
+            .collect()
+    }
+
+    pub fn to_record(&self) -> ManifestRecord {
+        let mut bundles = BTreeMap::<u64, BundleRecord>::new();
+        for row in self.get_chunk_rows() {
+            bundles
+                .entry(row.bundle_id.0)
+                .or_insert_with(|| BundleRecord {
+                    id: row.bundle_id,
+                    chunks: Vec::new(),
+                })
+                .chunks
+                .push(ChunkRecord {
+                    id: row.id,
+                    size_compressed: row.size_compressed,
+                    size_uncompressed: row.size_uncompressed,
+                });
+        }
+        let mut dirs = self
+            .dirs
+            .values()
+            .filter(|dir| !dir.is_root())
+            .map(|dir| dir.path.clone())
+            .collect::<Vec<_>>();
+        dirs.sort();
+        ManifestRecord {
+            id: HexId(self.id),
+            langs: self.get_lang_records(),
+            bundles: bundles.into_values().collect(),
+            dirs,
+            files: self.files.iter().map(|file| file.to_record(self)).collect(),
+        }
+    }
+
+    pub fn export<W: io::Write>(
+        &self,
+        writer: &mut W,
+        format: ExportFormat,
+        table: ExportTable,
+    ) -> Result<(), Error> {
+        match table {
+            ExportTable::Manifest => match format {
+                ExportFormat::Json => serde_json::to_writer_pretty(&mut *writer, &self.to_record())
+                    .map_err(io::Error::from)
+                    .and_then(|_| writeln!(writer))
+                    .map_err(|err| Error::io("Failed to write export!", err)),
+                ExportFormat::Ndjson => write_rows(writer, format, &[self.to_record()]),
+                ExportFormat::Csv => throw(Error::validation(
+                    "Manifest table can not be exported as CSV!",
+                )),
+            },
+            ExportTable::Files => {
+                let rows = self
+                    .files
+                    .iter()
+                    .map(|file| file.to_row(self))
+                    .collect::<Vec<_>>();
+                write_rows(writer, format, &rows)
+            }
+            ExportTable::Chunks => write_rows(writer, format, &self.get_chunk_rows()),
+            ExportTable::Bundles => write_rows(writer, format, &self.get_bundle_rows()),
+            ExportTable::FileChunks => write_rows(writer, format, &self.get_file_chunk_rows()),
+            ExportTable::Langs => write_rows(writer, format, &self.get_lang_records()),
+        }
+    }
+}
+
+fn insert_import_dir<'a>(paths: &mut BTreeSet<&'a str>, path: &'a str) {
+    let mut end = 0;
+    for name in path.split('/').filter(|_| !path.is_empty()) {
+        end += name.len();
+        paths.insert(&path[..end]);
+        end += 1;
+    }
+}
+
+fn get_import_path(path: &str) -> Result<&str, Error> {
+    let path = path.trim_matches('/');
+    if path.is_empty() {
+        return Ok(path);
+    }
+    for name in path.split('/') {
+        if name.is_empty() {
+            return throw(Error::validation("Bad path!").with_path(path));
+        }
+        verify_filename(name)?;
+    }
+    Ok(path)
+}
+
+fn get_import_dirs(record: &ManifestRecord) -> Result<HashMap<u64, Dir>, Error> {
+    let mut paths = BTreeSet::new();
+    for file in &record.files {
+        let name = get_import_path(&file.name)?;
+        if let Some((path, _)) = name.rsplit_once('/') {
+            insert_import_dir(&mut paths, path);
+        }
+    }
+    for path in &record.dirs {
+        insert_import_dir(&mut paths, get_import_path(path)?);
+    }
+    let mut ids = HashMap::new();
+    let mut dirs = HashMap::new();
+    ids.insert("", 0u64);
+    dirs.insert(
+        0,
+        Dir {
+            id: 0,
+            parent_id: None,
+            name: String::new(),
+            path: String::new(),
+            dirs: Vec::new(),
+        },
+    );
+    for (index, &path) in paths.iter().enumerate() {
+        let id = index as u64 + 1;
+        let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+        let parent_id = ids[parent];
+        ids.insert(path, id);
+        dirs.insert(
+            id,
+            Dir {
+                id,
+                parent_id: Some(parent_id),
+                name: name.to_string(),
+                path: path.to_string(),
+                dirs: Vec::new(),
+            },
+        );
+        if let Some(parent) = dirs.get_mut(&parent_id) {
+            parent.dirs.push(id);
+        }
+    }
+    let names = dirs
+        .values()
+        .map(|dir| (dir.id, dir.name.clone()))
+        .collect::<HashMap<_, _>>();
+    for dir in dirs.values_mut() {
+        dir.dirs.sort_by(|a, b| names[a].cmp(&names[b]));
+    }
+    Ok(dirs)
+}
+
+impl Manifest {
+    pub fn from_record(record: &ManifestRecord) -> Result<Self, Error> {
+        let mut langs = Vec::new();
+        let mut lang_ids = LangSet::NEUTRAL;
+        for lang in &record.langs {
+            verify_filename(&lang.name)?;
+            if !lang_ids.insert(lang.id) {
+                return throw(Error::validation(format!("Bad lang id {}!", lang.id)));
+            }
+            langs.push(Lang {
+                id: lang.id,
+                name: lang.name.clone(),
+            });
+        }
+        if lang_ids.len() != langs.len() {
+            return throw(Error::validation("Duplicate lang ids!"));
+        }
+        let mut chunks = HashMap::new();
+        for bundle in &record.bundles {
+            raw::verify_bundle_id(bundle.id.0)?;
+            let mut offset_compressed = 0;
+            for chunk in &bundle.chunks {
+                raw::verify_chunk_id(bundle.id.0, chunk.id.0)?;
+                let previous = chunks.insert(
+                    chunk.id.0,
+                    Chunk {
+                        chunk_id: chunk.id.0,
+                        bundle_id: bundle.id.0,
+                        size_compressed: chunk.size_compressed,
+                        size_uncompressed: chunk.size_uncompressed,
+                        offset_compressed,
+                        offset_uncompressed: 0,
+                    },
+                );
+                if previous.is_some() {
+                    return throw(
+                        Error::validation("Duplicate chunk id!")
+                            .with_bundle(bundle.id.0)
+                            .with_chunk(chunk.id.0),
+                    );
+                }
+                offset_compressed += chunk.size_compressed as u64;
+            }
+        }
+        let dirs = get_import_dirs(record)?;
+        let dir_ids = dirs
+            .values()
+            .map(|dir| (dir.path.as_str(), dir.id))
+            .collect::<HashMap<_, _>>();
+        let mut params = Vec::new();
+        let mut file_ids = HashSet::new();
+        let mut file_names = HashSet::new();
+        let mut files = Vec::with_capacity(record.files.len());
+        for file in &record.files {
+            if file.name.ends_with('/') {
+                return throw(
+                    Error::validation("Bad or duplicate file name!").with_path(&file.name),
+                );
+            }
+            let name = get_import_path(&file.name)?;
+            raw::verify_file_id(file.id.0).map_err(|err| err.with_path(name))?;
+            if !file_ids.insert(file.id.0) {
+                return throw(Error::validation("Duplicate file id!").with_path(name));
+            }
+            if name.is_empty() || !file_names.insert(name) {
+                return throw(Error::validation("Bad or duplicate file name!").with_path(name));
+            }
+            let hash_type = match HashType::from_name(&file.hash_type) {
+                Some(hash_type) => hash_type,
+                None => {
+                    return throw(
+                        Error::validation(format!("Bad hash type {}!", file.hash_type))
+                            .with_path(name),
+                    )
+                }
+            };
+            let mut file_langs = LangSet::NEUTRAL;
+            for lang_name in &file.langs {
+                match langs.iter().find(|lang| lang.is_named(lang_name)) {
+                    Some(lang) => file_langs.insert(lang.id),
+                    None => {
+                        return throw(
+                            Error::validation(format!("Unknown locale {}!", lang_name))
+                                .with_path(name),
+                        )
+                    }
+                };
+            }
+            let mut offset_uncompressed = 0;
+            let mut file_chunks = Vec::with_capacity(file.chunks.len());
+            for chunk_id in &file.chunks {
+                let chunk = match chunks.get(&chunk_id.0) {
+                    Some(chunk) => chunk,
+                    None => {
+                        return throw(
+                            Error::validation("Unknown chunk!")
+                                .with_path(name)
+                                .with_chunk(chunk_id.0),
+                        )
+                    }
+                };
+                if chunk.size_uncompressed > file.max_uncompressed {
+                    return throw(
+                        Error::validation("Chunk too big!")
+                            .with_path(name)
+                            .with_chunk(chunk_id.0),
+                    );
+                }
+                file_chunks.push(Chunk {
+                    offset_uncompressed,
+                    ..*chunk
+                });
+                offset_uncompressed += chunk.size_uncompressed as u64;
+            }
+            if file.size > raw::FILE_SIZE_LIMIT {
+                return throw(
+                    Error::validation("File goes over the 4GB manifest size limit!")
+                        .with_path(name),
+                );
+            }
+            if offset_uncompressed != file.size {
+                return throw(
+                    Error::validation("Chunk sizes do not add up to file size!").with_path(name),
+                );
+            }
+            let file_params = Params {
+                hash_type: hash_type as u8,
+                max_uncompressed: file.max_uncompressed,
+                ..Default::default()
+            };
+            raw::verify_params(&file_params).map_err(|err| err.with_path(name))?;
+            let params_index = match params.iter().position(|&p| p == file_params) {
+                Some(index) => index,
+                None => {
+                    params.push(file_params);
+                    params.len() - 1
+                }
+            };
+            let parent = name.rsplit_once('/').map_or("", |(parent, _)| parent);
+            let parent_id = match dir_ids.get(parent) {
+                Some(&parent_id) => parent_id,
+                None => return throw(Error::validation("Unknown parent dir!").with_path(name)),
+            };
+            files.push(File {
+                id: file.id.0,
+                name: name.to_string(),
+                link_name: file.link.clone(),
+                size: file.size,
+                max_uncompressed: file.max_uncompressed,
+                hash_type,
+                langs: file_langs,
+                chunks: file_chunks,
+                meta: FileMeta {
+                    parent_id,
+                    permissions: file.permissions,
+                    params_index: params_index as u8,
+                    params: file_params,
+                    ..Default::default()
+                },
+            });
+        }
+        let result = Self {
+            id: record.id.0,
+            integrity: Integrity::default(),
+            files,
+            langs,
+            dir_paths: get_dir_paths(&dirs),
+            dirs,
+            dir_problems: Vec::new(),
+            chunks,
+            keys: Vec::new(),
+        };
+        result.check_links()?;
+        Ok(result)
+    }
+
+    pub fn import<R: io::Read>(reader: &mut R) -> Result<Self, Error> {
+        let record = serde_json::from_reader::<_, ManifestRecord>(reader)
+            .map_err(|err| Error::parse(format!("Failed to parse manifest json: {}!", err)))?;
+        Self::from_record(&record)
+    }
+}
@@ -1,53 +1,99 @@
-use super::throw;
+use super::{throw, Error, ParseLimits};
+use std::marker::PhantomData;
 
 #[derive(Clone, Copy)]
 pub struct Ptr<'a> {
     pub data: &'a [u8],
     pub index: usize,
+    pub limits: &'a ParseLimits,
 }
 
+#[derive(Clone, Copy)]
 pub struct Table<'a> {
     offset: Ptr<'a>,
-    fields: Vec<u16>,
+    vtable: Ptr<'a>,
+    fields: usize,
+}
+
+pub struct Vector<'a, T> {
+    offset: Ptr<'a>,
+    len: usize,
+    marker: PhantomData<T>,
 }
 
 pub trait ReadPtr<'a>: Sized {
     const SIZE: usize;
-    fn from_ptr(offset: Ptr<'a>) -> Result<Self, String>;
+    fn from_ptr(offset: Ptr<'a>) -> Result<Self, Error>;
+}
+
+#[derive(Default)]
+pub struct Builder {
+    data: Vec<u8>,
+}
+
+pub trait WritePtr {
+    fn size(&self) -> usize;
+    fn to_ptr(&self, builder: &mut Builder, at: usize);
+}
+
+impl<'a> Default for Ptr<'a> {
+    fn default() -> Self {
+        Self {
+            data: &[],
+            index: 0,
+            limits: &ParseLimits::DEFAULT,
+        }
+    }
 }
 
 impl<'a> Ptr<'a> {
-    pub fn new(data: &'a [u8], index: usize) -> Result<Self, String> {
+    pub fn new(data: &'a [u8], index: usize) -> Result<Self, Error> {
+        Self::with_limits(data, index, &ParseLimits::DEFAULT)
+    }
+
+    pub fn with_limits(
+        data: &'a [u8],
+        index: usize,
+        limits: &'a ParseLimits,
+    ) -> Result<Self, Error> {
         if index > data.len() {
-            throw("Index out of range!")
+            throw(Error::parse("Index out of range!").with_offset(index as u64))
         } else {
-            Ok(Self { data, index })
+            Ok(Self {
+                data,
+                index,
+                limits,
+            })
         }
     }
 
-    pub fn get<T: ReadPtr<'a>>(self) -> Result<T, String> {
+    pub fn get<T: ReadPtr<'a>>(self) -> Result<T, Error> {
         ReadPtr::from_ptr(self)
     }
 
-    pub fn add_relative(self, relative: isize) -> Result<Option<Self>, String> {
+    pub fn error<S: ToString>(self, message: S) -> Error {
+        Error::parse(message).with_offset(self.index as u64)
+    }
+
+    pub fn add_relative(self, relative: isize) -> Result<Option<Self>, Error> {
         let data = self.data;
         let index = self.index;
         if relative < 0 {
             if -relative as usize > self.index {
-                throw("Failed to add relative would underflow")
+                throw(self.error("Failed to add relative would underflow"))
             } else {
                 Ok(Some(Ptr {
-                    data,
                     index: index - (-relative as usize),
+                    ..self
                 }))
             }
         } else if relative > 0 {
             if relative as usize > data.len() - index {
-                throw("Failed to add relative would overflow")
+                throw(self.error("Failed to add relative would overflow"))
             } else {
                 Ok(Some(Ptr {
-                    data,
                     index: index + (relative as usize),
+                    ..self
                 }))
             }
         } else {
@@ -55,26 +101,102 @@ impl<'a> Ptr<'a> {
         }
     }
 
-    pub fn add_offset(self, offset: usize) -> Result<Self, String> {
+    pub fn add_offset(self, offset: usize) -> Result<Self, Error> {
         let data = self.data;
         let index = self.index;
         if offset > data.len() - index {
-            throw("Failed to add offset would overflow!")
+            throw(self.error("Failed to add offset would overflow!"))
         } else {
             Ok(Ptr {
-                data,
                 index: index + offset,
+                ..self
             })
         }
     }
 }
 
+impl Builder {
+    pub fn finish<T: WritePtr>(root: &T) -> Vec<u8> {
+        let mut builder = Self::default();
+        let at = builder.reserve(u32::SIZE, u32::SIZE);
+        root.to_ptr(&mut builder, at);
+        builder.data
+    }
+
+    pub fn align(&mut self, align: usize) {
+        let size = self.data.len().div_ceil(align) * align;
+        self.data.resize(size, 0u8);
+    }
+
+    pub fn reserve(&mut self, size: usize, align: usize) -> usize {
+        self.align(align);
+        let at = self.data.len();
+        self.data.resize(at + size, 0u8);
+        at
+    }
+
+    pub fn put(&mut self, at: usize, bytes: &[u8]) {
+        self.data[at..][..bytes.len()].copy_from_slice(bytes);
+    }
+
+    pub fn put_offset(&mut self, at: usize, target: usize) {
+        self.put(at, &((target - at) as u32).to_le_bytes());
+    }
+
+    pub fn write_table(&mut self, at: usize, fields: &[&dyn WritePtr]) {
+        let vtable_size = 4 + 2 * fields.len();
+        let vtable = self.reserve(vtable_size, u16::SIZE);
+        let table = self.reserve(i32::SIZE, i32::SIZE);
+        self.put(table, &((table - vtable) as i32).to_le_bytes());
+        let mut positions = Vec::with_capacity(fields.len());
+        for (index, field) in fields.iter().enumerate() {
+            let position = self.reserve(field.size(), field.size());
+            self.put(
+                vtable + 4 + 2 * index,
+                &((position - table) as u16).to_le_bytes(),
+            );
+            positions.push(position);
+        }
+        let table_size = self.data.len() - table;
+        self.put(vtable, &(vtable_size as u16).to_le_bytes());
+        self.put(vtable + 2, &(table_size as u16).to_le_bytes());
+        self.put_offset(at, table);
+        for (field, position) in fields.iter().zip(positions) {
+            field.to_ptr(self, position);
+        }
+    }
+}
+
 impl<'a> Table<'a> {
-    pub fn get_ptr(&self, index: usize) -> Result<Option<Ptr<'a>>, String> {
-        if index >= self.fields.len() {
+    pub fn ptr(&self) -> Ptr<'a> {
+        self.offset
+    }
+
+    pub fn len(&self) -> usize {
+        self.fields
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields == 0
+    }
+
+    pub fn get_size(&self) -> Result<usize, Error> {
+        Ok(self.vtable.add_offset(2)?.get::<u16>()? as usize)
+    }
+
+    pub fn get_field_offset(&self, index: usize) -> Result<usize, Error> {
+        if index >= self.fields {
+            Ok(0)
+        } else {
+            Ok(self.vtable.add_offset(4 + 2 * index)?.get::<u16>()? as usize)
+        }
+    }
+
+    pub fn get_ptr(&self, index: usize) -> Result<Option<Ptr<'a>>, Error> {
+        if index >= self.fields {
             Ok(None)
         } else {
-            let offset = self.fields[index] as usize;
+            let offset = self.get_field_offset(index)?;
             if offset == 0 {
                 Ok(None)
             } else {
@@ -83,7 +205,7 @@ impl<'a> Table<'a> {
         }
     }
 
-    pub fn get<T: ReadPtr<'a>>(&self, index: usize) -> Result<Option<T>, String> {
+    pub fn get<T: ReadPtr<'a>>(&self, index: usize) -> Result<Option<T>, Error> {
         Ok(if let Some(ptr) = self.get_ptr(index)? {
             Some(T::from_ptr(ptr)?)
         } else {
@@ -91,7 +213,7 @@ impl<'a> Table<'a> {
         })
     }
 
-    pub fn get_or_default<T: ReadPtr<'a> + Default>(&self, index: usize) -> Result<T, String> {
+    pub fn get_or_default<T: ReadPtr<'a> + Default>(&self, index: usize) -> Result<T, Error> {
         Ok(if let Some(ptr) = self.get_ptr(index)? {
             T::from_ptr(ptr)?
         } else {
@@ -99,232 +221,354 @@ impl<'a> Table<'a> {
         })
     }
 
-    pub fn get_or_error<T: ReadPtr<'a> + Default>(&self, index: usize) -> Result<T, String> {
+    pub fn get_or_error<T: ReadPtr<'a> + Default>(&self, index: usize) -> Result<T, Error> {
         if let Some(ptr) = self.get_ptr(index)? {
             T::from_ptr(ptr)
         } else {
-            throw("Can not be null!")
+            throw(self.offset.error("Can not be null!"))
+        }
+    }
+}
+
+impl<'a, T> Clone for Vector<'a, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T> Copy for Vector<'a, T> {}
+
+impl<'a, T> Default for Vector<'a, T> {
+    fn default() -> Self {
+        Self {
+            offset: Ptr::default(),
+            len: 0,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T: ReadPtr<'a> + 'a> Vector<'a, T> {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, index: usize) -> Result<T, Error> {
+        if index >= self.len {
+            throw(self.offset.error("Vector index out of range!"))
+        } else {
+            T::from_ptr(self.offset.add_offset(index * T::SIZE)?)
         }
     }
+
+    pub fn iter(&self) -> impl Iterator<Item = Result<T, Error>> + 'a {
+        let vector = *self;
+        (0..vector.len).map(move |index| vector.get(index))
+    }
 }
 
 impl<'a> ReadPtr<'a> for bool {
     const SIZE: usize = std::mem::size_of::<Self>();
-    fn from_ptr(offset: Ptr<'a>) -> Result<Self, String> {
+    fn from_ptr(offset: Ptr<'a>) -> Result<Self, Error> {
         if let Ok(result) = u8::from_ptr(offset) {
             Ok(result != 0)
         } else {
-            throw("Failed to read bool")
+            throw(offset.error("Failed to read bool"))
         }
     }
 }
 
 impl<'a> ReadPtr<'a> for u8 {
     const SIZE: usize = std::mem::size_of::<Self>();
-    fn from_ptr(offset: Ptr<'a>) -> Result<Self, String> {
+    fn from_ptr(offset: Ptr<'a>) -> Result<Self, Error> {
         if offset.data.len() - offset.index >= Self::SIZE {
             let mut buffer = [0u8; Self::SIZE];
             buffer[..Self::SIZE].copy_from_slice(&offset.data[offset.index..][..Self::SIZE]);
             Ok(Self::from_le_bytes(buffer))
         } else {
-            throw("Failed to read num")
+            throw(offset.error("Failed to read num"))
         }
     }
 }
 
 impl<'a> ReadPtr<'a> for i8 {
     const SIZE: usize = std::mem::size_of::<Self>();
-    fn from_ptr(offset: Ptr<'a>) -> Result<Self, String> {
+    fn from_ptr(offset: Ptr<'a>) -> Result<Self, Error> {
         if offset.data.len() - offset.index >= Self::SIZE {
             let mut buffer = [0u8; Self::SIZE];
             buffer[..Self::SIZE].copy_from_slice(&offset.data[offset.index..][..Self::SIZE]);
             Ok(Self::from_le_bytes(buffer))
         } else {
-            throw("Failed to read num")
+            throw(offset.error("Failed to read num"))
         }
     }
 }
 
 impl<'a> ReadPtr<'a> for u16 {
     const SIZE: usize = std::mem::size_of::<Self>();
-    fn from_ptr(offset: Ptr<'a>) -> Result<Self, String> {
+    fn from_ptr(offset: Ptr<'a>) -> Result<Self, Error> {
         if offset.data.len() - offset.index >= Self::SIZE {
             let mut buffer = [0u8; Self::SIZE];
             buffer[..Self::SIZE].copy_from_slice(&offset.data[offset.index..][..Self::SIZE]);
             Ok(Self::from_le_bytes(buffer))
         } else {
-            throw("Failed to read num")
+            throw(offset.error("Failed to read num"))
         }
     }
 }
 
 impl<'a> ReadPtr<'a> for i16 {
     const SIZE: usize = std::mem::size_of::<Self>();
-    fn from_ptr(offset: Ptr<'a>) -> Result<Self, String> {
+    fn from_ptr(offset: Ptr<'a>) -> Result<Self, Error> {
         if offset.data.len() - offset.index >= Self::SIZE {
             let mut buffer = [0u8; Self::SIZE];
             buffer[..Self::SIZE].copy_from_slice(&offset.data[offset.index..][..Self::SIZE]);
             Ok(Self::from_le_bytes(buffer))
         } else {
-            throw("Failed to read num")
+            throw(offset.error("Failed to read num"))
         }
     }
 }
 
 impl<'a> ReadPtr<'a> for u32 {
     const SIZE: usize = std::mem::size_of::<Self>();
-    fn from_ptr(offset: Ptr<'a>) -> Result<Self, String> {
+    fn from_ptr(offset: Ptr<'a>) -> Result<Self, Error> {
         if offset.data.len() - offset.index >= Self::SIZE {
             let mut buffer = [0u8; Self::SIZE];
             buffer[..Self::SIZE].copy_from_slice(&offset.data[offset.index..][..Self::SIZE]);
             Ok(Self::from_le_bytes(buffer))
         } else {
-            throw("Failed to read num")
+            throw(offset.error("Failed to read num"))
         }
     }
 }
 
 impl<'a> ReadPtr<'a> for i32 {
     const SIZE: usize = std::mem::size_of::<Self>();
-    fn from_ptr(offset: Ptr<'a>) -> Result<Self, String> {
+    fn from_ptr(offset: Ptr<'a>) -> Result<Self, Error> {
         if offset.data.len() - offset.index >= Self::SIZE {
             let mut buffer = [0u8; Self::SIZE];
             buffer[..Self::SIZE].copy_from_slice(&offset.data[offset.index..][..Self::SIZE]);
             Ok(Self::from_le_bytes(buffer))
         } else {
-            throw("Failed to read num")
+            throw(offset.error("Failed to read num"))
         }
     }
 }
 
 impl<'a> ReadPtr<'a> for u64 {
     const SIZE: usize = std::mem::size_of::<Self>();
-    fn from_ptr(offset: Ptr<'a>) -> Result<Self, String> {
+    fn from_ptr(offset: Ptr<'a>) -> Result<Self, Error> {
         if offset.data.len() - offset.index >= Self::SIZE {
             let mut buffer = [0u8; Self::SIZE];
             buffer[..Self::SIZE].copy_from_slice(&offset.data[offset.index..][..Self::SIZE]);
             Ok(Self::from_le_bytes(buffer))
         } else {
-            throw("Failed to read num")
+            throw(offset.error("Failed to read num"))
         }
     }
 }
 
 impl<'a> ReadPtr<'a> for i64 {
     const SIZE: usize = std::mem::size_of::<Self>();
-    fn from_ptr(offset: Ptr<'a>) -> Result<Self, String> {
+    fn from_ptr(offset: Ptr<'a>) -> Result<Self, Error> {
         if offset.data.len() - offset.index >= Self::SIZE {
             let mut buffer = [0u8; Self::SIZE];
             buffer[..Self::SIZE].copy_from_slice(&offset.data[offset.index..][..Self::SIZE]);
             Ok(Self::from_le_bytes(buffer))
         } else {
-            throw("Failed to read num")
+            throw(offset.error("Failed to read num"))
         }
     }
 }
 
 impl<'a> ReadPtr<'a> for f32 {
     const SIZE: usize = std::mem::size_of::<Self>();
-    fn from_ptr(offset: Ptr<'a>) -> Result<Self, String> {
+    fn from_ptr(offset: Ptr<'a>) -> Result<Self, Error> {
         if offset.data.len() - offset.index >= Self::SIZE {
             let mut buffer = [0u8; Self::SIZE];
             buffer[..Self::SIZE].copy_from_slice(&offset.data[offset.index..][..Self::SIZE]);
             Ok(Self::from_le_bytes(buffer))
         } else {
-            throw("Failed to read num")
+            throw(offset.error("Failed to read num"))
         }
     }
 }
 
 impl<'a> ReadPtr<'a> for f64 {
     const SIZE: usize = std::mem::size_of::<Self>();
-    fn from_ptr(offset: Ptr<'a>) -> Result<Self, String> {
+    fn from_ptr(offset: Ptr<'a>) -> Result<Self, Error> {
         if offset.data.len() - offset.index >= Self::SIZE {
             let mut buffer = [0u8; Self::SIZE];
             buffer[..Self::SIZE].copy_from_slice(&offset.data[offset.index..][..Self::SIZE]);
             Ok(Self::from_le_bytes(buffer))
         } else {
-            throw("Failed to read num")
+            throw(offset.error("Failed to read num"))
         }
     }
 }
 
 impl<'a> ReadPtr<'a> for Option<Ptr<'a>> {
     const SIZE: usize = i32::SIZE;
-    fn from_ptr(offset: Ptr<'a>) -> Result<Self, String> {
+    fn from_ptr(offset: Ptr<'a>) -> Result<Self, Error> {
         let relative = i32::from_ptr(offset)?;
         offset.add_relative(relative as isize)
     }
 }
 
-impl<'a> ReadPtr<'a> for String {
+impl<'a> ReadPtr<'a> for &'a str {
     const SIZE: usize = Option::<Ptr>::SIZE;
-    fn from_ptr(offset: Ptr<'a>) -> Result<Self, String> {
+    fn from_ptr(offset: Ptr<'a>) -> Result<Self, Error> {
         if let Some(offset) = Option::<Ptr>::from_ptr(offset)? {
             let size = u32::from_ptr(offset)? as usize;
+            if size > offset.limits.max_string_length {
+                return throw(
+                    Error::validation(format!(
+                        "String length {} exceeds limit {}!",
+                        size, offset.limits.max_string_length
+                    ))
+                    .with_offset(offset.index as u64),
+                );
+            }
             let data = offset.data;
             let index = offset.index + Self::SIZE;
             if data.len() - index >= size {
                 if let Ok(result) = std::str::from_utf8(&data[index..][..size]) {
-                    Ok(result.to_string())
+                    Ok(result)
                 } else {
-                    throw("Failed to read str encoding")
+                    throw(offset.error("Failed to read str encoding"))
                 }
             } else {
-                throw("Failed to read str data")
+                throw(offset.error("Failed to read str data"))
             }
         } else {
-            Ok("".to_string())
+            Ok("")
         }
     }
 }
 
-impl<'a, T: ReadPtr<'a>> ReadPtr<'a> for Vec<T> {
+impl<'a> ReadPtr<'a> for String {
+    const SIZE: usize = Option::<Ptr>::SIZE;
+    fn from_ptr(offset: Ptr<'a>) -> Result<Self, Error> {
+        Ok(<&str>::from_ptr(offset)?.to_string())
+    }
+}
+
+impl<'a, T: ReadPtr<'a>> ReadPtr<'a> for Vector<'a, T> {
     const SIZE: usize = Option::<Ptr>::SIZE;
-    fn from_ptr(offset: Ptr<'a>) -> Result<Self, String> {
+    fn from_ptr(offset: Ptr<'a>) -> Result<Self, Error> {
         if let Some(offset) = Option::<Ptr>::from_ptr(offset)? {
-            let size = u32::from_ptr(offset)? as usize;
-            let mut offset = offset.add_offset(u32::SIZE)?;
-            if (offset.data.len() - offset.index) / T::SIZE >= size {
-                let mut results = Vec::new();
-                for _ in 0..size {
-                    results.push(T::from_ptr(offset)?);
-                    offset = offset.add_offset(T::SIZE)?;
-                }
-                Ok(results)
+            let len = u32::from_ptr(offset)? as usize;
+            let offset = offset.add_offset(u32::SIZE)?;
+            if (offset.data.len() - offset.index) / T::SIZE >= len {
+                Ok(Self {
+                    offset,
+                    len,
+                    marker: PhantomData,
+                })
             } else {
-                throw("Not enough storage for vector!")
+                throw(offset.error("Not enough storage for vector!"))
             }
         } else {
-            Ok(Vec::new())
+            Ok(Self::default())
         }
     }
 }
 
+impl<'a, T: ReadPtr<'a> + 'a> ReadPtr<'a> for Vec<T> {
+    const SIZE: usize = Option::<Ptr>::SIZE;
+    fn from_ptr(offset: Ptr<'a>) -> Result<Self, Error> {
+        Vector::<T>::from_ptr(offset)?.iter().collect()
+    }
+}
+
 impl<'a> ReadPtr<'a> for Table<'a> {
     const SIZE: usize = Option::<Ptr>::SIZE;
-    fn from_ptr(offset: Ptr<'a>) -> Result<Self, String> {
+    fn from_ptr(offset: Ptr<'a>) -> Result<Self, Error> {
         if let Some(offset) = Option::<Ptr>::from_ptr(offset)? {
             let vtable_relative = i32::from_ptr(offset)? as isize;
             if let Some(vtable_offset) = offset.add_relative(-vtable_relative)? {
-                let vtable_size = u16::from_ptr(vtable_offset)?;
+                let vtable_size = u16::from_ptr(vtable_offset)? as usize;
                 if vtable_size >= 4 {
-                    let mut vtable_field_offset = vtable_offset.add_offset(4)?;
-                    let mut fields = Vec::new();
-                    for _ in 0..(vtable_size - 4) / 2 {
-                        let vtable_field = u16::from_ptr(vtable_field_offset)?;
-                        fields.push(vtable_field);
-                        vtable_field_offset = vtable_field_offset.add_offset(2)?
+                    if vtable_size > vtable_offset.data.len() - vtable_offset.index {
+                        return throw(
+                            offset.error("Failed to read table because vtable is out of range"),
+                        );
                     }
-                    Ok(Self { offset, fields })
+                    Ok(Self {
+                        offset,
+                        vtable: vtable_offset,
+                        fields: (vtable_size - 4) / 2,
+                    })
                 } else {
-                    throw("Failed to read table because vtable is too small")
+                    throw(offset.error("Failed to read table because vtable is too small"))
                 }
             } else {
-                throw("Vtable size offset can not be null!")
+                throw(offset.error("Vtable size offset can not be null!"))
             }
         } else {
-            throw("Table can not be null!")
+            throw(offset.error("Table can not be null!"))
+        }
+    }
+}
+
+macro_rules! impl_write_num {
+    ($($t:ty),*) => {
+        $(
+            impl WritePtr for $t {
+                fn size(&self) -> usize {
+                    std::mem::size_of::<Self>()
+                }
+                fn to_ptr(&self, builder: &mut Builder, at: usize) {
+                    builder.put(at, &self.to_le_bytes());
+                }
+            }
+        )*
+    };
+}
+
+impl_write_num!(u8, i8, u16, i16, u32, i32, u64, i64, f32, f64);
+
+impl WritePtr for bool {
+    fn size(&self) -> usize {
+        u8::SIZE
+    }
+    fn to_ptr(&self, builder: &mut Builder, at: usize) {
+        (*self as u8).to_ptr(builder, at)
+    }
+}
+
+impl WritePtr for String {
+    fn size(&self) -> usize {
+        u32::SIZE
+    }
+    fn to_ptr(&self, builder: &mut Builder, at: usize) {
+        let offset = builder.reserve(u32::SIZE + self.len() + 1, u32::SIZE);
+        builder.put(offset, &(self.len() as u32).to_le_bytes());
+        builder.put(offset + u32::SIZE, self.as_bytes());
+        builder.put_offset(at, offset);
+    }
+}
+
+impl<T: WritePtr> WritePtr for Vec<T> {
+    fn size(&self) -> usize {
+        u32::SIZE
+    }
+    fn to_ptr(&self, builder: &mut Builder, at: usize) {
+        let item_size = self.first().map_or(u32::SIZE, |item| item.size());
+        let align = item_size.max(u32::SIZE);
+        while !(builder.data.len() + u32::SIZE).is_multiple_of(align) {
+            builder.data.push(0u8);
+        }
+        let offset = builder.reserve(u32::SIZE + item_size * self.len(), u32::SIZE);
+        builder.put(offset, &(self.len() as u32).to_le_bytes());
+        builder.put_offset(at, offset);
+        for (index, item) in self.iter().enumerate() {
+            item.to_ptr(builder, offset + u32::SIZE + item_size * index);
         }
     }
 }
@@ -0,0 +1,383 @@
+use super::{
+    fb::{Ptr, ReadPtr, Table},
+    raw::ManifestData,
+    Error, ParseLimits,
+};
+use std::{fmt::Write, io};
+
+#[derive(Clone, Debug)]
+pub enum Value {
+    Table(Node),
+    String(String),
+    Vector { len: usize, items: Vec<Value> },
+    Scalars { len: usize, bytes: Vec<u8> },
+}
+
+#[derive(Clone, Debug)]
+pub struct Slot {
+    pub index: usize,
+    pub offset: usize,
+    pub bytes: Vec<u8>,
+    pub target: Option<Value>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Node {
+    pub position: usize,
+    pub size: usize,
+    pub slots: Vec<Slot>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Inspector {
+    pub max_depth: usize,
+    pub max_items: usize,
+    pub max_bytes: usize,
+}
+
+impl Default for Inspector {
+    fn default() -> Self {
+        Self {
+            max_depth: 16,
+            max_items: 64,
+            max_bytes: 256,
+        }
+    }
+}
+
+impl Inspector {
+    pub fn inspect(&self, data: &[u8]) -> Result<Node, Error> {
+        let table = Ptr::new(data, 0)?.get::<Table>()?;
+        self.inspect_table(table, 0)
+    }
+
+    pub fn inspect_manifest<R: io::Read>(
+        &self,
+        reader: &mut R,
+        limits: &ParseLimits,
+    ) -> Result<Node, Error> {
+        self.inspect(ManifestData::read(reader, limits)?.data())
+    }
+
+    fn inspect_table(&self, table: Table, depth: usize) -> Result<Node, Error> {
+        let ptr = table.ptr();
+        let size = table.get_size()?;
+        let mut offsets = (0..table.len())
+            .map(|index| table.get_field_offset(index))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut ends = offsets.clone();
+        ends.push(size);
+        ends.sort_unstable();
+        let mut slots = Vec::with_capacity(offsets.len());
+        for (index, offset) in offsets.drain(..).enumerate() {
+            if offset == 0 {
+                slots.push(Slot {
+                    index,
+                    offset,
+                    bytes: Vec::new(),
+                    target: None,
+                });
+                continue;
+            }
+            let end = ends
+                .iter()
+                .copied()
+                .find(|&end| end > offset)
+                .unwrap_or(offset)
+                .max(offset);
+            let start = ptr.index + offset;
+            let bytes = ptr
+                .data
+                .get(start..(ptr.index + end).min(ptr.data.len()))
+                .unwrap_or_default()
+                .to_vec();
+            let target = if bytes.len() == u32::SIZE && depth < self.max_depth {
+                self.guess_offset(ptr.add_offset(offset)?, depth + 1)
+            } else {
+                None
+            };
+            slots.push(Slot {
+                index,
+                offset,
+                bytes,
+                target,
+            });
+        }
+        Ok(Node {
+            position: ptr.index,
+            size,
+            slots,
+        })
+    }
+
+    fn is_table(ptr: Ptr) -> bool {
+        let table = match ptr.get::<Table>() {
+            Ok(table) => table,
+            Err(_) => return false,
+        };
+        let size = match table.get_size() {
+            Ok(size) => size,
+            Err(_) => return false,
+        };
+        if size < i32::SIZE || size > table.ptr().data.len() - table.ptr().index {
+            return false;
+        }
+        (0..table.len()).all(|index| match table.get_field_offset(index) {
+            Ok(offset) => offset == 0 || (offset >= i32::SIZE && offset < size),
+            Err(_) => false,
+        })
+    }
+
+    fn is_string(ptr: Ptr) -> bool {
+        match (ptr.get::<&str>(), Option::<Ptr>::from_ptr(ptr)) {
+            (Ok(string), Ok(Some(target))) if !string.is_empty() => {
+                let end = target.index + u32::SIZE + string.len();
+                target.data.get(end) == Some(&0) && !string.chars().any(char::is_control)
+            }
+            _ => false,
+        }
+    }
+
+    fn guess_offset(&self, ptr: Ptr, depth: usize) -> Option<Value> {
+        let target = Option::<Ptr>::from_ptr(ptr).ok()??;
+        if Self::is_string(ptr) {
+            return Some(Value::String(ptr.get::<String>().ok()?));
+        }
+        if Self::is_table(ptr) {
+            let table = ptr.get::<Table>().ok()?;
+            return self.inspect_table(table, depth).ok().map(Value::Table);
+        }
+        let len = u32::from_ptr(target).ok()? as usize;
+        let items = target.add_offset(u32::SIZE).ok()?;
+        if len > items.data.len() - items.index {
+            return None;
+        }
+        let count = len.min(self.max_items);
+        let elements = (0..count)
+            .map(|index| items.add_offset(index * u32::SIZE))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap_or_default();
+        if len != 0 && elements.len() == count {
+            if elements.iter().all(|&element| Self::is_string(element)) {
+                let items = elements
+                    .iter()
+                    .filter_map(|element| element.get::<String>().ok())
+                    .map(Value::String)
+                    .collect();
+                return Some(Value::Vector { len, items });
+            }
+            if elements.iter().all(|&element| Self::is_table(element)) {
+                let items = elements
+                    .iter()
+                    .filter_map(|element| element.get::<Table>().ok())
+                    .filter_map(|table| self.inspect_table(table, depth).ok())
+                    .map(Value::Table)
+                    .collect();
+                return Some(Value::Vector { len, items });
+            }
+        }
+        let size = (items.data.len() - items.index)
+            .min(len * u64::SIZE)
+            .min(self.max_bytes);
+        Some(Value::Scalars {
+            len,
+            bytes: items.data[items.index..][..size].to_vec(),
+        })
+    }
+}
+
+impl Slot {
+    pub fn is_present(&self) -> bool {
+        self.offset != 0
+    }
+
+    pub fn scalars(&self) -> Vec<(&'static str, String)> {
+        let bytes = &self.bytes;
+        let mut results = Vec::new();
+        if bytes.len() >= 8 {
+            let mut buffer = [0u8; 8];
+            buffer.copy_from_slice(&bytes[..8]);
+            results.push(("u64", u64::from_le_bytes(buffer).to_string()));
+            results.push(("i64", i64::from_le_bytes(buffer).to_string()));
+            let float = f64::from_le_bytes(buffer);
+            if float.is_normal() {
+                results.push(("f64", float.to_string()));
+            }
+        } else if bytes.len() >= 4 {
+            let mut buffer = [0u8; 4];
+            buffer.copy_from_slice(&bytes[..4]);
+            results.push(("u32", u32::from_le_bytes(buffer).to_string()));
+            results.push(("i32", i32::from_le_bytes(buffer).to_string()));
+            let float = f32::from_le_bytes(buffer);
+            if float.is_normal() {
+                results.push(("f32", float.to_string()));
+            }
+        } else if bytes.len() >= 2 {
+            let mut buffer = [0u8; 2];
+            buffer.copy_from_slice(&bytes[..2]);
+            results.push(("u16", u16::from_le_bytes(buffer).to_string()));
+            results.push(("i16", i16::from_le_bytes(buffer).to_string()));
+        } else if let Some(&byte) = bytes.first() {
+            results.push(("u8", byte.to_string()));
+            results.push(("i8", (byte as i8).to_string()));
+        }
+        results
+    }
+}
+
+fn write_hex(output: &mut String, bytes: &[u8]) {
+    for byte in bytes {
+        let _ = write!(output, "{:02X}", byte);
+    }
+}
+
+fn write_json_string(output: &mut String, value: &str) {
+    output.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(output, "\\u{:04x}", c as u32);
+            }
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+}
+
+impl Value {
+    fn write_tree(&self, output: &mut String, indent: usize) {
+        match self {
+            Value::Table(node) => node.write_tree(output, indent),
+            Value::String(value) => {
+                let _ = writeln!(output, "string {:?}", value);
+            }
+            Value::Vector { len, items } => {
+                let _ = writeln!(output, "vector[{}]", len);
+                for (index, item) in items.iter().enumerate() {
+                    let _ = write!(output, "{:indent$}[{}] ", "", index, indent = indent + 2);
+                    item.write_tree(output, indent + 4);
+                }
+            }
+            Value::Scalars { len, bytes } => {
+                let _ = write!(output, "vector[{}] of scalars", len);
+                if !bytes.is_empty() {
+                    output.push(' ');
+                    write_hex(output, bytes);
+                }
+                output.push('\n');
+            }
+        }
+    }
+
+    fn write_json(&self, output: &mut String) {
+        match self {
+            Value::Table(node) => {
+                output.push_str("{\"table\":");
+                node.write_json(output);
+                output.push('}');
+            }
+            Value::String(value) => {
+                output.push_str("{\"string\":");
+                write_json_string(output, value);
+                output.push('}');
+            }
+            Value::Vector { len, items } => {
+                let _ = write!(output, "{{\"vector\":{{\"len\":{},\"items\":[", len);
+                for (index, item) in items.iter().enumerate() {
+                    if index != 0 {
+                        output.push(',');
+                    }
+                    item.write_json(output);
+                }
+                output.push_str("]}}");
+            }
+            Value::Scalars { len, bytes } => {
+                let _ = write!(output, "{{\"scalars\":{{\"len\":{},\"bytes\":\"", len);
+                write_hex(output, bytes);
+                output.push_str("\"}}");
+            }
+        }
+    }
+}
+
+impl Node {
+    fn write_tree(&self, output: &mut String, indent: usize) {
+        let _ = writeln!(output, "table @{} size {}", self.position, self.size);
+        for slot in &self.slots {
+            let _ = write!(
+                output,
+                "{:indent$}[{}] ",
+                "",
+                slot.index,
+                indent = indent + 2
+            );
+            if !slot.is_present() {
+                output.push_str("absent\n");
+                continue;
+            }
+            let _ = write!(output, "+{} ", slot.offset);
+            write_hex(output, &slot.bytes);
+            for (name, value) in slot.scalars() {
+                let _ = write!(output, " {}={}", name, value);
+            }
+            if let Some(target) = &slot.target {
+                output.push_str(" -> ");
+                target.write_tree(output, indent + 4);
+            } else {
+                output.push('\n');
+            }
+        }
+    }
+
+    fn write_json(&self, output: &mut String) {
+        let _ = write!(
+            output,
+            "{{\"position\":{},\"size\":{},\"slots\":[",
+            self.position, self.size
+        );
+        for (index, slot) in self.slots.iter().enumerate() {
+            if index != 0 {
+                output.push(',');
+            }
+            let _ = write!(
+                output,
+                "{{\"index\":{},\"present\":{}",
+                slot.index,
+                slot.is_present()
+            );
+            if slot.is_present() {
+                let _ = write!(output, ",\"offset\":{},\"bytes\":\"", slot.offset);
+                write_hex(output, &slot.bytes);
+                output.push('"');
+                for (name, value) in slot.scalars() {
+                    let _ = write!(output, ",\"{}\":", name);
+                    if value.parse::<f64>().is_ok_and(f64::is_finite) {
+                        output.push_str(&value);
+                    } else {
+                        write_json_string(output, &value);
+                    }
+                }
+                if let Some(target) = &slot.target {
+                    output.push_str(",\"target\":");
+                    target.write_json(output);
+                }
+            }
+            output.push('}');
+        }
+        output.push_str("]}");
+    }
+
+    pub fn to_tree(&self) -> String {
+        let mut output = String::new();
+        self.write_tree(&mut output, 0);
+        output
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut output = String::new();
+        self.write_json(&mut output);
+        output
+    }
+}
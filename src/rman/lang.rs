@@ -0,0 +1,148 @@
+use super::{throw, Error, File};
+use std::{fmt, ops::BitOr};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Lang {
+    pub id: u8,
+    pub name: String,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct LangSet {
+    pub flags: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LangFilter {
+    pub neutral: bool,
+    pub langs: LangSet,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct LangInfo {
+    pub lang: Option<Lang>,
+    pub file_count: usize,
+    pub total_size: u64,
+}
+
+impl Lang {
+    pub const NEUTRAL: &'static str = "neutral";
+
+    pub fn is_named(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name)
+    }
+}
+
+impl LangSet {
+    pub const NEUTRAL: Self = Self { flags: 0 };
+    pub const ALL: Self = Self { flags: u64::MAX };
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        if (1..=64).contains(&id) {
+            Some(Self {
+                flags: 1 << (id - 1),
+            })
+        } else {
+            None
+        }
+    }
+
+    pub fn is_neutral(&self) -> bool {
+        self.flags == 0
+    }
+
+    pub fn contains(&self, id: u8) -> bool {
+        Self::from_id(id).is_some_and(|lang| self.intersects(lang))
+    }
+
+    pub fn intersects(&self, other: Self) -> bool {
+        self.flags & other.flags != 0
+    }
+
+    pub fn insert(&mut self, id: u8) -> bool {
+        match Self::from_id(id) {
+            Some(lang) => {
+                self.flags |= lang.flags;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.flags.count_ones() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.is_neutral()
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = u8> {
+        let flags = self.flags;
+        (0..64u8)
+            .filter(move |bit| flags & (1 << bit) != 0)
+            .map(|bit| bit + 1)
+    }
+}
+
+impl BitOr for LangSet {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self {
+            flags: self.flags | other.flags,
+        }
+    }
+}
+
+impl LangFilter {
+    pub const ALL: Self = Self {
+        neutral: true,
+        langs: LangSet::ALL,
+    };
+
+    pub fn parse(expression: &str, langs: &[Lang]) -> Result<Self, Error> {
+        let mut result = Self::default();
+        for name in expression
+            .split(|c: char| c == '+' || c == ',' || c.is_whitespace())
+            .filter(|name| !name.is_empty())
+        {
+            if name.eq_ignore_ascii_case(Lang::NEUTRAL) || name.eq_ignore_ascii_case("none") {
+                result.neutral = true;
+            } else if name == "*" || name.eq_ignore_ascii_case("all") {
+                result = Self::ALL;
+            } else if let Some(lang) = langs.iter().find(|lang| lang.is_named(name)) {
+                result.langs.insert(lang.id);
+            } else {
+                return throw(Error::validation(format!("Unknown locale {}!", name)));
+            }
+        }
+        Ok(result)
+    }
+
+    pub fn matches(&self, langs: LangSet) -> bool {
+        if langs.is_neutral() {
+            self.neutral
+        } else {
+            self.langs.intersects(langs)
+        }
+    }
+
+    pub fn matches_file(&self, file: &File) -> bool {
+        self.matches(file.langs)
+    }
+}
+
+impl LangInfo {
+    pub fn name(&self) -> &str {
+        self.lang
+            .as_ref()
+            .map_or(Lang::NEUTRAL, |lang| lang.name.as_str())
+    }
+}
+
+impl fmt::Display for Lang {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
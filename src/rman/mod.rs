@@ -1,45 +1,127 @@
+mod cache;
+mod diff;
 mod dl;
+pub mod error;
+#[cfg(feature = "export")]
+mod export;
 mod fb;
+pub mod inspect;
+mod lang;
+mod prune;
 mod raw;
-use core::fmt::Display;
+mod select;
+mod stats;
+mod update;
+mod verify;
+pub use cache::*;
+pub use diff::*;
 pub use dl::*;
-use sha2::{Sha256, Sha512, Digest};
+pub use error::Error;
+#[cfg(feature = "export")]
+pub use export::*;
+pub use lang::*;
+pub use prune::*;
+pub use raw::{Integrity, Params};
+pub use select::*;
+use sha2::{Digest, Sha256, Sha512};
+pub use stats::*;
 use std::{
     collections::{BTreeMap, BTreeSet, HashMap, HashSet},
-    convert::{TryFrom, TryInto},
-    fs,
+    convert::TryFrom,
+    fmt, fs,
     io::{self, Read},
+    path::Path,
 };
-use ureq;
+pub use update::*;
+pub use verify::*;
 
-fn throw<T, S: std::string::ToString>(msg: S) -> Result<T, String> {
-    Err(msg.to_string())
+fn throw<T>(error: Error) -> Result<T, Error> {
+    Err(error)
 }
 
-fn re_throw<T, S: Display, E: Display>(value: Result<T, E>, msg: S) -> Result<T, String> {
-    match value {
-        Ok(value) => Ok(value),
-        Err(err) => throw(format!("{}: {}", msg, err)),
+fn get_dir_paths(dirs: &HashMap<u64, Dir>) -> HashMap<String, u64> {
+    dirs.values()
+        .map(|dir| (dir.path.clone(), dir.id))
+        .collect()
+}
+
+fn resolve_link(name: &str, link_name: &str) -> Result<String, Error> {
+    resolve_link_with(name, link_name, |_| false)
+}
+
+fn resolve_link_with<F: Fn(&str) -> bool>(
+    name: &str,
+    link_name: &str,
+    is_link: F,
+) -> Result<String, Error> {
+    let outside = || Error::validation("Link target goes outside the root!").with_path(name);
+    if link_name.starts_with('/') || link_name.starts_with('\\') || link_name.contains(':') {
+        return throw(outside());
     }
+    let mut parts = name.split('/').collect::<Vec<_>>();
+    parts.pop();
+    let mut through_link = false;
+    for part in link_name.split(['/', '\\']) {
+        if through_link {
+            return throw(Error::validation("Link target goes through a link!").with_path(name));
+        }
+        match part {
+            "" | "." => {}
+            ".." => {
+                if parts.pop().is_none() {
+                    return throw(outside());
+                }
+            }
+            part => {
+                parts.push(part);
+                through_link = is_link(&parts.join("/"));
+            }
+        }
+    }
+    Ok(parts.join("/"))
 }
 
 #[repr(u8)]
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
 pub enum HashType {
+    #[default]
     NONE,
     SHA512,
     SHA256,
     HKDF,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseLimits {
+    pub max_compressed: u32,
+    pub max_decompressed: u32,
+    pub max_files: usize,
+    pub max_chunks: usize,
+    pub max_string_length: usize,
+    pub max_dir_depth: usize,
+}
+
 #[derive(Clone, Copy, Debug, Default)]
 pub struct Chunk {
     pub chunk_id: u64,
     pub bundle_id: u64,
     pub size_compressed: u32,
     pub size_uncompressed: u32,
-    pub offset_compressed: u32,
-    pub offset_uncompressed: u32,
+    pub offset_compressed: u64,
+    pub offset_uncompressed: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FileMeta {
+    pub parent_id: u64,
+    pub permissions: u8,
+    pub unk5: u8,
+    pub unk6: u8,
+    pub unk8: u8,
+    pub unk10: u8,
+    pub params_index: u8,
+    pub params: Params,
 }
 
 #[derive(Clone, Debug)]
@@ -47,22 +129,71 @@ pub struct File {
     pub id: u64,
     pub name: String,
     pub link_name: String,
-    pub size: u32,
+    pub size: u64,
     pub max_uncompressed: u32,
     pub hash_type: HashType,
-    pub langs: HashSet<String>,
+    pub langs: LangSet,
     pub chunks: Vec<Chunk>,
+    pub meta: FileMeta,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Dir {
+    pub id: u64,
+    pub parent_id: Option<u64>,
+    pub name: String,
+    pub path: String,
+    pub dirs: Vec<u64>,
+}
+
+pub struct DirIter<'a> {
+    manifest: &'a Manifest,
+    stack: Vec<u64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyGuess {
+    File,
+    Bundle,
+    Chunk,
+    Unknown,
+    Undecodable,
+}
+
+#[derive(Clone, Debug)]
+pub struct Key {
+    pub id: u64,
+    pub data: Vec<u8>,
+    pub guess: KeyGuess,
 }
 
 #[derive(Clone, Debug)]
 pub struct Manifest {
     pub id: u64,
+    pub integrity: Integrity,
     pub files: Vec<File>,
+    pub langs: Vec<Lang>,
+    pub dirs: HashMap<u64, Dir>,
+    pub dir_paths: HashMap<String, u64>,
+    pub dir_problems: Vec<String>,
+    pub chunks: HashMap<u64, Chunk>,
+    pub keys: Vec<Key>,
+}
+
+impl ParseLimits {
+    pub const DEFAULT: Self = Self {
+        max_compressed: 256 * 1024 * 1024,
+        max_decompressed: 1024 * 1024 * 1024,
+        max_files: 1024 * 1024,
+        max_chunks: 16 * 1024 * 1024,
+        max_string_length: 4096,
+        max_dir_depth: 256,
+    };
 }
 
-impl Default for HashType {
+impl Default for ParseLimits {
     fn default() -> Self {
-        HashType::NONE
+        Self::DEFAULT
     }
 }
 
@@ -92,13 +223,13 @@ impl HashType {
             opad[i] ^= key[i];
         }
         let index = u32::to_be_bytes(1);
-        let mut buffer = Sha256::new().chain(&ipad).chain(&index).finalize();
-        buffer = Sha256::new().chain(&opad).chain(&buffer).finalize();
+        let mut buffer = Sha256::new().chain(ipad).chain(index).finalize();
+        buffer = Sha256::new().chain(opad).chain(buffer).finalize();
         let mut result = [0u8; 8];
         result.copy_from_slice(&buffer[..8]);
         for _ in 0..31 {
-            buffer = Sha256::new().chain(&ipad).chain(&buffer).finalize();
-            buffer = Sha256::new().chain(&opad).chain(&buffer).finalize();
+            buffer = Sha256::new().chain(ipad).chain(buffer).finalize();
+            buffer = Sha256::new().chain(opad).chain(buffer).finalize();
             for i in 0..8 {
                 result[i] ^= buffer[i];
             }
@@ -106,6 +237,27 @@ impl HashType {
         u64::from_le_bytes(result)
     }
 
+    pub fn name(self) -> &'static str {
+        match self {
+            HashType::NONE => "none",
+            HashType::SHA512 => "sha512",
+            HashType::SHA256 => "sha256",
+            HashType::HKDF => "hkdf",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [
+            HashType::NONE,
+            HashType::SHA512,
+            HashType::SHA256,
+            HashType::HKDF,
+        ]
+        .iter()
+        .copied()
+        .find(|hash_type| hash_type.name().eq_ignore_ascii_case(name))
+    }
+
     pub fn compute(self, input: &[u8]) -> u64 {
         match self {
             Self::NONE => 0,
@@ -117,19 +269,74 @@ impl HashType {
 }
 
 impl TryFrom<u8> for HashType {
-    type Error = String;
+    type Error = Error;
     fn try_from(value: u8) -> Result<Self, Self::Error> {
         match value {
             0 => Ok(HashType::NONE),
             1 => Ok(HashType::SHA512),
             2 => Ok(HashType::SHA256),
             3 => Ok(HashType::HKDF),
-            _ => throw("Bad HashType"),
+            _ => throw(Error::validation(format!("Bad hash type {}!", value))),
         }
     }
 }
 
 impl File {
+    pub const PERMISSION_EXECUTABLE: u8 = 1;
+
+    pub fn get_dir_path(&self) -> &str {
+        self.name.rsplit_once('/').map_or("", |(parent, _)| parent)
+    }
+
+    pub fn is_link(&self) -> bool {
+        !self.link_name.is_empty()
+    }
+
+    pub fn is_executable(&self) -> bool {
+        self.meta.permissions & File::PERMISSION_EXECUTABLE != 0
+    }
+
+    pub fn get_mode(&self) -> u32 {
+        if self.is_link() {
+            0o777
+        } else if self.is_executable() {
+            0o755
+        } else {
+            0o644
+        }
+    }
+
+    pub fn get_mode_string(&self) -> String {
+        let mode = self.get_mode();
+        let mut result = String::with_capacity(10);
+        result.push(if self.is_link() { 'l' } else { '-' });
+        for shift in [6, 3, 0] {
+            let bits = mode >> shift;
+            result.push(if bits & 4 != 0 { 'r' } else { '-' });
+            result.push(if bits & 2 != 0 { 'w' } else { '-' });
+            result.push(if bits & 1 != 0 { 'x' } else { '-' });
+        }
+        result
+    }
+
+    pub fn get_link_target(&self) -> Result<String, Error> {
+        resolve_link(&self.name, &self.link_name)
+    }
+
+    pub fn get_file_name(&self) -> &str {
+        self.name
+            .rsplit_once('/')
+            .map_or(&self.name, |(_, name)| name)
+    }
+
+    fn get_raw_params(&self) -> Params {
+        Params {
+            hash_type: self.hash_type as u8,
+            max_uncompressed: self.max_uncompressed,
+            ..self.meta.params
+        }
+    }
+
     pub fn download_if<F: FnMut(&Chunk) -> bool>(&self, mut check_chunk: F) -> DownloadFile {
         let name = self.name.to_string();
         let size = self.size;
@@ -156,6 +363,8 @@ impl File {
         }
         DownloadFile {
             name,
+            link_name: self.link_name.clone(),
+            mode: self.get_mode(),
             size,
             max_uncompressed,
             bundles,
@@ -169,12 +378,15 @@ impl File {
     pub fn download_checked<R: io::Read + io::Seek>(&self, reader: &mut R) -> DownloadFile {
         let mut buffer = Vec::with_capacity(self.max_uncompressed as usize);
         self.download_if(|chunk| {
-            if let Ok(_) = reader.seek(io::SeekFrom::Start(chunk.offset_uncompressed as u64)) {
+            if reader
+                .seek(io::SeekFrom::Start(chunk.offset_uncompressed))
+                .is_ok()
+            {
                 buffer.resize(chunk.size_uncompressed as usize, 0u8);
-                if let Ok(_) = reader.read_exact(&mut buffer) {
-                    if self.hash_type.compute(&buffer) == chunk.chunk_id {
-                        return false;
-                    }
+                if reader.read_exact(&mut buffer).is_ok()
+                    && self.hash_type.compute(&buffer) == chunk.chunk_id
+                {
+                    return false;
                 }
             }
             true
@@ -182,19 +394,36 @@ impl File {
     }
 
     pub fn download_checked_in_dir(&self, dir: &str) -> DownloadFile {
-        if let Ok(mut file) = fs::File::open(format!("{}/{}", dir, &self.name)) {
+        if self.is_link() {
+            self.download_if(|_| false)
+        } else if let Ok(mut file) = fs::File::open(format!("{}/{}", dir, &self.name)) {
             self.download_checked(&mut file)
         } else {
             self.download_all()
         }
     }
 
+    pub fn download_checked_in_dir_with_cache(&self, cache: &VerifyCache) -> DownloadFile {
+        if cache.is_good_in_dir(self) {
+            self.download_if(|_| false)
+        } else {
+            self.download_checked_in_dir(&cache.dir)
+        }
+    }
+
+    pub fn verify_with_cache(&self, cache: &VerifyCache) -> bool {
+        cache.is_good_in_dir(self) || self.verify(&cache.dir)
+    }
+
     pub fn verify(&self, dir: &str) -> bool {
-        if let Ok(mut file) = fs::File::open(format!("{}/{}", dir, &self.name)) {
+        if self.is_link() {
+            fs::read_link(format!("{}/{}", dir, &self.name))
+                .is_ok_and(|target| target == Path::new(&self.link_name))
+        } else if let Ok(mut file) = fs::File::open(format!("{}/{}", dir, &self.name)) {
             let mut buffer = Vec::with_capacity(self.max_uncompressed as usize);
             for chunk in &self.chunks {
                 buffer.resize(chunk.size_uncompressed as usize, 0u8);
-                if let Ok(_) = file.read_exact(&mut buffer) {
+                if file.read_exact(&mut buffer).is_ok() {
                     if self.hash_type.compute(&buffer) != chunk.chunk_id {
                         return false;
                     }
@@ -202,33 +431,153 @@ impl File {
                     return false;
                 }
             }
-            true
+            file.metadata()
+                .is_ok_and(|metadata| self.has_mode(&metadata))
         } else {
             false
         }
     }
+
+    #[cfg(unix)]
+    pub fn has_mode(&self, metadata: &fs::Metadata) -> bool {
+        use std::os::unix::fs::PermissionsExt;
+        (metadata.permissions().mode() & 0o111 != 0) == self.is_executable()
+    }
+
+    #[cfg(not(unix))]
+    pub fn has_mode(&self, _metadata: &fs::Metadata) -> bool {
+        true
+    }
+}
+
+impl fmt::Display for File {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {:>12} {}",
+            self.get_mode_string(),
+            self.size,
+            self.name
+        )?;
+        if self.is_link() {
+            write!(f, " -> {}", self.link_name)?;
+        }
+        Ok(())
+    }
+}
+
+impl Dir {
+    pub fn is_root(&self) -> bool {
+        self.parent_id.is_none()
+    }
+
+    pub fn join(&self, name: &str) -> String {
+        if self.path.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}/{}", self.path, name)
+        }
+    }
+
+    pub fn contains(&self, file: &File) -> bool {
+        file.get_dir_path() == self.path
+    }
+}
+
+impl<'a> Iterator for DirIter<'a> {
+    type Item = &'a Dir;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(id) = self.stack.pop() {
+            if let Some(dir) = self.manifest.dirs.get(&id) {
+                self.stack.extend(dir.dirs.iter().rev());
+                return Some(dir);
+            }
+        }
+        None
+    }
+}
+
+impl Key {
+    pub fn is_decoded(&self) -> bool {
+        self.guess != KeyGuess::Undecodable
+    }
+
+    pub fn may_relate_to(&self, file: &File) -> bool {
+        match self.guess {
+            KeyGuess::File => file.id == self.id,
+            KeyGuess::Bundle => file.chunks.iter().any(|chunk| chunk.bundle_id == self.id),
+            KeyGuess::Chunk => file.chunks.iter().any(|chunk| chunk.chunk_id == self.id),
+            KeyGuess::Unknown | KeyGuess::Undecodable => false,
+        }
+    }
 }
 
 impl Manifest {
-    pub fn read<R: io::Read>(reader: &mut R) -> Result<Self, String> {
-        let raw = raw::Manifest::read(reader)?;
-        let mut files = Vec::new();
-        for file in &raw.files {
-            let id = file.id;
-            let name = raw.get_file_name(&file.name, file.parent_id)?;
-            let link_name = file.link.to_string();
-            let size = file.size;
-            let params = raw.get_params(file.params_index)?;
-            let hash_type = params.hash_type.try_into()?;
-            let langs = raw.get_langs(file.lang_flags)?;
+    pub fn read<R: io::Read>(reader: &mut R) -> Result<Self, Error> {
+        Self::read_with_limits(reader, &ParseLimits::DEFAULT)
+    }
+
+    pub fn read_with_limits<R: io::Read>(
+        reader: &mut R,
+        limits: &ParseLimits,
+    ) -> Result<Self, Error> {
+        let data = raw::ManifestData::read(reader, limits)?;
+        let raw = data.view()?;
+        let index = &raw.index;
+        let mut dir_problems = Vec::new();
+        let dirs = index.get_dirs(&mut dir_problems)?;
+        let langs = index.get_lang_list();
+        let mut files = Vec::with_capacity(raw.files.len());
+        for file in raw.files.iter() {
+            let file = file?;
+            let id = file.id()?;
+            let name = match dirs.get(&file.parent_id()?) {
+                Some(dir) => dir.join(file.name()?),
+                None => {
+                    return throw(
+                        Error::validation("Failed to find dir by id!").with_path(file.name()?),
+                    )
+                }
+            };
+            let link_name = file.link()?.to_string();
+            let size = file.size()? as u64;
+            let params_index = file.params_index()?;
+            let params = index.get_params(params_index)?;
+            let hash_type =
+                HashType::try_from(params.hash_type).map_err(|error| error.with_path(&name))?;
+            let langs = index
+                .get_langs(file.lang_flags()?)
+                .map_err(|error| error.with_path(&name))?;
             let max_uncompressed = params.max_uncompressed;
-            let chunks = raw.get_chunks(&file.chunk_ids)?;
+            let chunks = index
+                .get_chunks(file.chunk_ids()?.iter())
+                .map_err(|error| error.with_path(&name))?;
             for chunk in &chunks {
                 if chunk.size_uncompressed > max_uncompressed {
-                    return throw("Chunk too big!");
+                    return throw(
+                        Error::validation("Chunk too big!")
+                            .with_path(&name)
+                            .with_bundle(chunk.bundle_id)
+                            .with_chunk(chunk.chunk_id),
+                    );
+                }
+                let end = chunk.offset_uncompressed + chunk.size_uncompressed as u64;
+                if end > raw::FILE_SIZE_LIMIT {
+                    return throw(
+                        Error::validation("File goes over the 4GB manifest size limit!")
+                            .with_path(&name)
+                            .with_chunk(chunk.chunk_id)
+                            .with_offset(chunk.offset_uncompressed),
+                    );
                 }
-                if chunk.offset_uncompressed + chunk.size_uncompressed > size {
-                    return throw("Chunk would go outside the file!");
+                if end > size {
+                    return throw(
+                        Error::validation("Chunk would go outside the file!")
+                            .with_path(&name)
+                            .with_chunk(chunk.chunk_id)
+                            .with_offset(chunk.offset_uncompressed),
+                    );
                 }
             }
             files.push(File {
@@ -240,19 +589,468 @@ impl Manifest {
                 hash_type,
                 langs,
                 chunks,
+                meta: FileMeta {
+                    parent_id: file.parent_id()?,
+                    permissions: file.permissions()?,
+                    unk5: file.unk5()?,
+                    unk6: file.unk6()?,
+                    unk8: file.unk8()?,
+                    unk10: file.unk10()?,
+                    params_index,
+                    params,
+                },
+            });
+        }
+        let file_ids = files.iter().map(|file| file.id).collect::<HashSet<_>>();
+        let chunks = raw.index.chunks;
+        let bundle_ids = chunks
+            .values()
+            .map(|chunk| chunk.bundle_id)
+            .collect::<HashSet<_>>();
+        let mut keys = Vec::new();
+        let raw_keys = raw.keys.map(|raw_keys| raw_keys.iter().collect::<Vec<_>>());
+        for key in raw_keys.unwrap_or_else(|error| vec![Err(error)]) {
+            let key = match key.and_then(|key| key.decode()) {
+                Ok(key) => key,
+                Err(_) => {
+                    keys.push(Key {
+                        id: 0,
+                        data: Vec::new(),
+                        guess: KeyGuess::Undecodable,
+                    });
+                    continue;
+                }
+            };
+            let guess = if file_ids.contains(&key.id) {
+                KeyGuess::File
+            } else if bundle_ids.contains(&key.id) {
+                KeyGuess::Bundle
+            } else if chunks.contains_key(&key.id) {
+                KeyGuess::Chunk
+            } else {
+                KeyGuess::Unknown
+            };
+            keys.push(Key {
+                id: key.id,
+                data: key.data,
+                guess,
             });
         }
-        Ok(Self { id: raw.id, files })
+        let result = Self {
+            id: raw.id,
+            integrity: raw.integrity,
+            files,
+            langs,
+            dir_paths: get_dir_paths(&dirs),
+            dirs,
+            dir_problems,
+            chunks,
+            keys,
+        };
+        result.check_links()?;
+        Ok(result)
     }
 
-    pub fn download(agent: &mut ureq::Agent, url: &str) -> Result<Self, String> {
+    fn check_links(&self) -> Result<(), Error> {
+        let links = self
+            .files
+            .iter()
+            .filter(|file| file.is_link())
+            .map(|file| file.name.to_lowercase())
+            .collect::<HashSet<_>>();
+        let is_link = |path: &str| links.contains(&path.to_lowercase());
+        for dir in self.dirs.values() {
+            if is_link(&dir.path) {
+                return throw(
+                    Error::validation("Dir path goes through a link!").with_path(&dir.path),
+                );
+            }
+        }
+        for file in self.files.iter().filter(|file| file.is_link()) {
+            resolve_link_with(&file.name, &file.link_name, is_link)?;
+        }
+        Ok(())
+    }
+
+    pub fn diff<'a>(&'a self, new: &'a Manifest) -> ManifestDiff<'a> {
+        ManifestDiff::new(self, new)
+    }
+
+    pub fn stats(&self) -> ManifestStats<'_> {
+        ManifestStats::new(self, ManifestStats::LARGEST_FILES)
+    }
+
+    pub fn index(&self) -> FileIndex<'_> {
+        FileIndex::new(self)
+    }
+
+    pub fn select(&self, selector: &Selector) -> Selection<'_> {
+        Selection::new(self, selector)
+    }
+
+    pub fn select_all(&self) -> Selection<'_> {
+        self.select(&Selector::new())
+    }
+
+    pub fn get_lang(&self, id: u8) -> Option<&Lang> {
+        self.langs.iter().find(|lang| lang.id == id)
+    }
+
+    pub fn get_lang_by_name(&self, name: &str) -> Option<&Lang> {
+        self.langs.iter().find(|lang| lang.is_named(name))
+    }
+
+    pub fn get_lang_names(&self, langs: LangSet) -> Vec<&str> {
+        if langs.is_neutral() {
+            return vec![Lang::NEUTRAL];
+        }
+        langs
+            .ids()
+            .filter_map(|id| self.get_lang(id))
+            .map(|lang| lang.name.as_str())
+            .collect()
+    }
+
+    pub fn get_lang_infos(&self) -> Vec<LangInfo> {
+        let mut results = vec![LangInfo::default()];
+        results.extend(self.langs.iter().map(|lang| LangInfo {
+            lang: Some(lang.clone()),
+            ..Default::default()
+        }));
+        for info in &mut results {
+            let filter = match &info.lang {
+                Some(lang) => LangFilter {
+                    neutral: false,
+                    langs: LangSet::from_id(lang.id).unwrap_or_default(),
+                },
+                None => LangFilter {
+                    neutral: true,
+                    langs: LangSet::NEUTRAL,
+                },
+            };
+            for file in self.files.iter().filter(|file| filter.matches_file(file)) {
+                info.file_count += 1;
+                info.total_size += file.size;
+            }
+        }
+        results
+    }
+
+    pub fn parse_lang_filter(&self, expression: &str) -> Result<LangFilter, Error> {
+        LangFilter::parse(expression, &self.langs)
+    }
+
+    pub fn get_root(&self) -> Option<&Dir> {
+        self.dirs.values().find(|dir| dir.is_root())
+    }
+
+    pub fn get_dir(&self, id: u64) -> Option<&Dir> {
+        self.dirs.get(&id)
+    }
+
+    pub fn get_dir_by_path(&self, path: &str) -> Option<&Dir> {
+        let id = self.dir_paths.get(path.trim_matches('/'))?;
+        self.dirs.get(id)
+    }
+
+    pub fn get_parent(&self, dir: &Dir) -> Option<&Dir> {
+        self.dirs.get(&dir.parent_id?)
+    }
+
+    pub fn get_file_dir(&self, file: &File) -> Option<&Dir> {
+        self.get_dir_by_path(file.get_dir_path())
+    }
+
+    pub fn get_dir_dirs(&self, dir: &Dir) -> Vec<&Dir> {
+        dir.dirs.iter().filter_map(|id| self.dirs.get(id)).collect()
+    }
+
+    pub fn get_dir_files(&self, dir: &Dir) -> Vec<&File> {
+        let mut files = self
+            .files
+            .iter()
+            .filter(|file| dir.contains(file))
+            .collect::<Vec<_>>();
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+        files
+    }
+
+    pub fn get_empty_dirs(&self) -> Vec<&Dir> {
+        let mut used = HashSet::new();
+        for file in &self.files {
+            let mut path = file.get_dir_path();
+            while used.insert(path) && !path.is_empty() {
+                path = path.rsplit_once('/').map_or("", |(parent, _)| parent);
+            }
+        }
+        self.walk_dirs()
+            .filter(|dir| !used.contains(dir.path.as_str()))
+            .collect()
+    }
+
+    pub fn walk_dirs(&self) -> DirIter<'_> {
+        self.walk_dirs_from(self.get_root())
+    }
+
+    pub fn walk_dirs_from<'a>(&'a self, dir: Option<&Dir>) -> DirIter<'a> {
+        DirIter {
+            manifest: self,
+            stack: dir.map(|dir| dir.id).into_iter().collect(),
+        }
+    }
+
+    pub fn get_key_files(&self, key: &Key) -> Vec<&File> {
+        self.files
+            .iter()
+            .filter(|file| key.may_relate_to(file))
+            .collect()
+    }
+
+    pub fn check_keys(&self) -> Result<(), Error> {
+        let undecodable = self.keys.iter().filter(|key| !key.is_decoded()).count();
+        if undecodable != 0 {
+            throw(Error::validation(format!(
+                "Manifest has {} key entries, {} could not be decoded!",
+                self.keys.len(),
+                undecodable
+            )))
+        } else if let Some(key) = self.keys.first() {
+            throw(Error::validation(format!(
+                "Manifest has {} unsupported key entries, first is {:016X} (guessed {:?}, {} files)!",
+                self.keys.len(),
+                key.id,
+                key.guess,
+                self.get_key_files(key).len()
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn to_raw_body(&self) -> Result<raw::Body, Error> {
+        let mut dir_names = BTreeMap::new();
+        let mut params = Vec::new();
+        let mut bundles = BTreeMap::new();
+        for dir in self.dirs.values() {
+            let parent_id = dir.parent_id.unwrap_or(dir.id);
+            dir_names.insert(dir.path.clone(), (dir.id, parent_id, dir.name.clone()));
+        }
+        dir_names
+            .entry(String::new())
+            .or_insert((0u64, 0u64, String::new()));
+        let mut next_id = self.dirs.keys().max().map_or(1, |&id| id + 1);
+        for file in &self.files {
+            let mut parent = String::new();
+            if let Some((dirs, _)) = file.name.rsplit_once('/') {
+                for name in dirs.split('/') {
+                    let path = if parent.is_empty() {
+                        name.to_string()
+                    } else {
+                        format!("{}/{}", parent, name)
+                    };
+                    let parent_id = dir_names[&parent].0;
+                    dir_names.entry(path.clone()).or_insert_with(|| {
+                        next_id += 1;
+                        (next_id - 1, parent_id, name.to_string())
+                    });
+                    parent = path;
+                }
+            }
+            let file_params = file.get_raw_params();
+            if !params.contains(&file_params) {
+                params.push(file_params);
+            }
+        }
+        let file_chunks = self.files.iter().flat_map(|file| file.chunks.iter());
+        for chunk in self.chunks.values().chain(file_chunks) {
+            bundles
+                .entry(chunk.bundle_id)
+                .or_insert_with(BTreeMap::new)
+                .insert(
+                    chunk.offset_compressed,
+                    raw::BundleChunk {
+                        id: chunk.chunk_id,
+                        size_compressed: chunk.size_compressed,
+                        size_uncompressed: chunk.size_uncompressed,
+                    },
+                );
+        }
+        for (bundle_id, chunks) in &bundles {
+            let mut offset_compressed = 0;
+            for (&offset, chunk) in chunks {
+                if offset != offset_compressed {
+                    return throw(
+                        Error::validation("Bundle is missing chunks!")
+                            .with_bundle(*bundle_id)
+                            .with_offset(offset_compressed),
+                    );
+                }
+                offset_compressed += chunk.size_compressed as u64;
+            }
+        }
+        let mut lang_ids = LangSet::NEUTRAL;
+        for lang in &self.langs {
+            if !lang_ids.insert(lang.id) {
+                return throw(Error::validation(format!("Bad lang id {}!", lang.id)));
+            }
+        }
+        if lang_ids.len() != self.langs.len() {
+            return throw(Error::validation("Duplicate lang ids!"));
+        }
+        if params.len() > u8::MAX as usize + 1 {
+            return throw(Error::validation("Too many params!"));
+        }
+        if !self.keys.iter().all(Key::is_decoded) {
+            return throw(Error::validation("Can not write undecodable keys!"));
+        }
+        let mut files = Vec::new();
+        for file in &self.files {
+            let (parent, name) = file.name.rsplit_once('/').unwrap_or(("", &file.name));
+            if file.langs.flags & !lang_ids.flags != 0 {
+                return throw(
+                    Error::validation("File references unknown lang!").with_path(&file.name),
+                );
+            }
+            let file_params = file.get_raw_params();
+            files.push(raw::File {
+                id: file.id,
+                parent_id: dir_names[parent].0,
+                size: match u32::try_from(file.size) {
+                    Ok(size) => size,
+                    Err(_) => {
+                        return throw(
+                            Error::validation("File goes over the 4GB manifest size limit!")
+                                .with_path(&file.name),
+                        )
+                    }
+                },
+                name: name.to_string(),
+                lang_flags: file.langs.flags,
+                link: file.link_name.clone(),
+                chunk_ids: file.chunks.iter().map(|chunk| chunk.chunk_id).collect(),
+                params_index: params.iter().position(|&p| p == file_params).unwrap_or(0) as u8,
+                unk5: file.meta.unk5,
+                unk6: file.meta.unk6,
+                unk8: file.meta.unk8,
+                unk10: file.meta.unk10,
+                permissions: file.meta.permissions,
+            });
+        }
+        let mut dirs = dir_names
+            .into_values()
+            .map(|(id, parent_id, name)| raw::Dir {
+                id,
+                parent_id,
+                name,
+            })
+            .collect::<Vec<_>>();
+        dirs.sort_by_key(|dir| dir.id);
+        Ok(raw::Body {
+            bundles: bundles
+                .into_iter()
+                .map(|(id, chunks)| raw::Bundle {
+                    id,
+                    chunks: chunks.into_values().collect(),
+                })
+                .collect(),
+            langs: self
+                .langs
+                .iter()
+                .map(|lang| raw::Lang {
+                    id: lang.id,
+                    name: lang.name.clone(),
+                })
+                .collect(),
+            files,
+            dirs,
+            keys: self
+                .keys
+                .iter()
+                .map(|key| raw::Key {
+                    id: key.id,
+                    data: key.data.clone(),
+                })
+                .collect(),
+            params,
+        })
+    }
+
+    pub fn write<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+        self.to_raw_body()?.write(writer, self.id)
+    }
+
+    pub fn check_roundtrip(&self) -> Result<(), Error> {
+        let mut first = Vec::new();
+        self.write(&mut first)?;
+        let mut second = Vec::new();
+        Self::read(&mut first.as_slice())?.write(&mut second)?;
+        if first == second {
+            Ok(())
+        } else {
+            throw(Error::integrity(
+                "Manifest does not survive a write and read!",
+            ))
+        }
+    }
+
+    pub fn read_verified<R: io::Read>(
+        reader: &mut R,
+        expected_id: u64,
+        limits: &ParseLimits,
+    ) -> Result<Self, Error> {
+        let manifest = Self::read_with_limits(reader, limits)?;
+        if manifest.id != expected_id {
+            return throw(Error::integrity(format!(
+                "Manifest id {:016X} does not match expected {:016X}!",
+                manifest.id, expected_id
+            )));
+        }
+        Ok(manifest)
+    }
+
+    pub fn id_from_url(url: &str) -> Option<u64> {
+        let name = url.rsplit('/').next()?;
+        let id = name.strip_suffix(".manifest")?;
+        if id.len() == 16 {
+            u64::from_str_radix(id, 16).ok()
+        } else {
+            None
+        }
+    }
+
+    pub fn download(agent: &mut ureq::Agent, url: &str) -> Result<Self, Error> {
+        Self::download_with_limits(agent, url, &ParseLimits::DEFAULT)
+    }
+
+    pub fn download_with_limits(
+        agent: &mut ureq::Agent,
+        url: &str,
+        limits: &ParseLimits,
+    ) -> Result<Self, Error> {
         if url.starts_with("https://") || url.starts_with("http://") {
-            let response = re_throw(agent.get(url).call(), "Failed to request manifest!")?;
+            let response = agent.get(url).call().map_err(|error| {
+                Error::network("Failed to request manifest!", error).with_path(url)
+            })?;
             let mut reader = response.into_reader();
-            Self::read(reader.by_ref())
+            Self::read_from_url(reader.by_ref(), url, limits)
+        } else {
+            let mut file = fs::File::open(url).map_err(|error| {
+                Error::io("Failed to open manifest file!", error).with_path(url)
+            })?;
+            Self::read_from_url(&mut file, url, limits)
+        }
+    }
+
+    fn read_from_url<R: io::Read>(
+        reader: &mut R,
+        url: &str,
+        limits: &ParseLimits,
+    ) -> Result<Self, Error> {
+        if let Some(expected_id) = Self::id_from_url(url) {
+            Self::read_verified(reader, expected_id, limits)
         } else {
-            let mut file = re_throw(fs::File::open(url), "Failed to open manifest file!")?;
-            Self::read(&mut file)
+            Self::read_with_limits(reader, limits)
         }
+        .map_err(|error| error.with_path(url))
     }
 }
@@ -0,0 +1,156 @@
+use super::{Error, Selection, VerifyCache};
+use std::{collections::HashSet, fmt, fs, path::Path};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StaleEntry {
+    pub path: String,
+    pub is_dir: bool,
+    pub size: u64,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ScanReport {
+    pub dir: String,
+    pub entries: Vec<StaleEntry>,
+}
+
+struct Scanner {
+    files: HashSet<String>,
+    dirs: HashSet<String>,
+}
+
+fn insert_dir_path(dirs: &mut HashSet<String>, path: &str) {
+    let mut path = path.to_lowercase();
+    while dirs.insert(path.clone()) && !path.is_empty() {
+        path = path
+            .rsplit_once('/')
+            .map_or("", |(parent, _)| parent)
+            .to_string();
+    }
+}
+
+fn get_dir_size(path: &Path) -> u64 {
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(_) => return 0,
+    };
+    entries
+        .filter_map(Result::ok)
+        .map(|entry| match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => get_dir_size(&entry.path()),
+            _ => entry.metadata().map_or(0, |metadata| metadata.len()),
+        })
+        .sum()
+}
+
+impl Scanner {
+    fn is_own_file(name: &str) -> bool {
+        name.strip_suffix(".tmp").unwrap_or(name) == VerifyCache::FILE_NAME
+    }
+
+    fn walk(&self, dir: &str, parent: &str, entries: &mut Vec<StaleEntry>) -> Result<(), Error> {
+        let path = if parent.is_empty() {
+            dir.to_string()
+        } else {
+            format!("{}/{}", dir, parent)
+        };
+        let mut children = fs::read_dir(&path)
+            .and_then(|children| children.collect::<Result<Vec<_>, _>>())
+            .map_err(|err| Error::io("Failed to read dir!", err).with_path(&path))?;
+        children.sort_by_key(|entry| entry.file_name());
+        for entry in children {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let path = if parent.is_empty() {
+                name.clone()
+            } else {
+                format!("{}/{}", parent, name)
+            };
+            if parent.is_empty() && Self::is_own_file(&name) {
+                continue;
+            }
+            let is_dir = entry.file_type().is_ok_and(|file_type| file_type.is_dir());
+            let path_lower = path.to_lowercase();
+            if is_dir && self.dirs.contains(&path_lower) {
+                self.walk(dir, &path, entries)?;
+            } else if is_dir {
+                entries.push(StaleEntry {
+                    size: get_dir_size(&entry.path()),
+                    path,
+                    is_dir,
+                });
+            } else if !self.files.contains(&path_lower) {
+                entries.push(StaleEntry {
+                    size: entry.metadata().map_or(0, |metadata| metadata.len()),
+                    path,
+                    is_dir,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ScanReport {
+    pub fn new(selection: &Selection, dir: &str) -> Result<Self, Error> {
+        let mut scanner = Scanner {
+            files: selection
+                .iter()
+                .map(|file| file.name.to_lowercase())
+                .collect(),
+            dirs: HashSet::new(),
+        };
+        for file in selection.iter() {
+            insert_dir_path(&mut scanner.dirs, file.get_dir_path());
+        }
+        for empty in selection.manifest.get_empty_dirs() {
+            insert_dir_path(&mut scanner.dirs, &empty.path);
+        }
+        let mut result = Self {
+            dir: dir.to_string(),
+            entries: Vec::new(),
+        };
+        scanner.walk(dir, "", &mut result.entries)?;
+        Ok(result)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get_total_size(&self) -> u64 {
+        self.entries.iter().map(|entry| entry.size).sum()
+    }
+
+    pub fn prune(&self) -> Result<(), Error> {
+        for entry in &self.entries {
+            let path = format!("{}/{}", self.dir, &entry.path);
+            let result = if entry.is_dir {
+                fs::remove_dir_all(&path)
+            } else {
+                fs::remove_file(&path)
+            };
+            result
+                .map_err(|err| Error::io("Failed to remove stale entry!", err).with_path(&path))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ScanReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in &self.entries {
+            if entry.is_dir {
+                writeln!(f, "D {:>14} {}/", entry.size, entry.path)?;
+            } else {
+                writeln!(f, "F {:>14} {}", entry.size, entry.path)?;
+            }
+        }
+        write!(
+            f,
+            "{} stale files, {} stale dirs, {} bytes",
+            self.entries.iter().filter(|entry| !entry.is_dir).count(),
+            self.entries.iter().filter(|entry| entry.is_dir).count(),
+            self.get_total_size()
+        )
+    }
+}
@@ -1,14 +1,14 @@
 use super::{
-    fb::{Ptr, ReadPtr, Table},
-    re_throw, throw, Chunk,
+    fb::{Builder, Ptr, ReadPtr, Table, Vector, WritePtr},
+    throw, Chunk, Error, ParseLimits,
 };
 use std::{
-    collections::{HashMap, HashSet},
-    io,
+    collections::{BTreeMap, HashMap, HashSet},
+    io::{self, Read},
 };
-use zstd;
 
 const CHUNK_LIMIT: u32 = 32 * 1024 * 1024;
+pub const FILE_SIZE_LIMIT: u64 = u32::MAX as u64;
 
 #[derive(Clone, Copy, Debug, Default)]
 pub struct BundleChunk {
@@ -53,10 +53,13 @@ pub struct Dir {
     pub name: String,
 }
 
-#[derive(Clone, Copy, Debug, Default)]
-pub struct Key {}
+#[derive(Clone, Debug, Default)]
+pub struct Key {
+    pub id: u64,
+    pub data: Vec<u8>,
+}
 
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub struct Params {
     pub unk0: u16,
     pub hash_type: u8,
@@ -75,14 +78,85 @@ pub struct Body {
     pub params: Vec<Params>,
 }
 
+#[derive(Clone, Copy)]
+pub struct BundleChunkView<'a> {
+    table: Table<'a>,
+}
+
+#[derive(Clone, Copy)]
+pub struct BundleView<'a> {
+    table: Table<'a>,
+}
+
+#[derive(Clone, Copy)]
+pub struct LangView<'a> {
+    table: Table<'a>,
+}
+
+#[derive(Clone, Copy)]
+pub struct DirView<'a> {
+    table: Table<'a>,
+}
+
+#[derive(Clone, Copy)]
+pub struct FileView<'a> {
+    table: Table<'a>,
+}
+
+#[derive(Clone, Copy)]
+pub struct KeyView<'a> {
+    table: Table<'a>,
+}
+
+#[derive(Clone, Copy)]
+pub struct ParamsView<'a> {
+    table: Table<'a>,
+}
+
+#[derive(Clone, Copy)]
+pub struct BodyView<'a> {
+    table: Table<'a>,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Integrity {
+    pub size_compressed: u32,
+    pub size_uncompressed: u32,
+    pub checked_content_size: bool,
+    pub checked_content_checksum: bool,
+}
+
 #[derive(Clone, Debug, Default)]
-pub struct Manifest {
-    pub id: u64,
-    pub files: Vec<File>,
-    chunks: HashMap<u64, Chunk>,
+pub struct Index {
+    pub chunks: HashMap<u64, Chunk>,
     langs: HashMap<u8, Lang>,
     dirs: HashMap<u64, Dir>,
     params: Vec<Params>,
+    limits: ParseLimits,
+}
+
+pub struct ManifestData {
+    pub id: u64,
+    pub integrity: Integrity,
+    data: Vec<u8>,
+    limits: ParseLimits,
+}
+
+pub struct ManifestView<'a> {
+    pub id: u64,
+    pub integrity: Integrity,
+    pub files: Vector<'a, FileView<'a>>,
+    pub keys: Result<Vector<'a, KeyView<'a>>, Error>,
+    pub index: Index,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Manifest {
+    pub id: u64,
+    pub integrity: Integrity,
+    pub files: Vec<File>,
+    pub keys: Vec<Key>,
+    pub index: Index,
 }
 
 struct Header {
@@ -95,304 +169,1253 @@ struct Header {
     pub size_uncompressed: u32,
 }
 
+impl<'a> ReadPtr<'a> for BundleChunkView<'a> {
+    const SIZE: usize = Table::SIZE;
+    fn from_ptr(offset: Ptr<'a>) -> Result<Self, Error> {
+        Ok(Self {
+            table: offset.get::<Table>()?,
+        })
+    }
+}
+
+impl<'a> BundleChunkView<'a> {
+    pub fn id(&self) -> Result<u64, Error> {
+        self.table.get_or_default(0)
+    }
+
+    pub fn size_compressed(&self) -> Result<u32, Error> {
+        self.table.get_or_default(1)
+    }
+
+    pub fn size_uncompressed(&self) -> Result<u32, Error> {
+        self.table.get_or_default(2)
+    }
+
+    pub fn decode(&self) -> Result<BundleChunk, Error> {
+        Ok(BundleChunk {
+            id: self.id()?,
+            size_compressed: self.size_compressed()?,
+            size_uncompressed: self.size_uncompressed()?,
+        })
+    }
+}
+
 impl<'a> ReadPtr<'a> for BundleChunk {
     const SIZE: usize = Table::SIZE;
-    fn from_ptr(offset: Ptr<'a>) -> Result<Self, String> {
-        let table = offset.get::<Table>()?;
+    fn from_ptr(offset: Ptr<'a>) -> Result<Self, Error> {
+        offset.get::<BundleChunkView>()?.decode()
+    }
+}
+
+impl<'a> ReadPtr<'a> for BundleView<'a> {
+    const SIZE: usize = Table::SIZE;
+    fn from_ptr(offset: Ptr<'a>) -> Result<Self, Error> {
         Ok(Self {
-            id: table.get_or_default(0)?,
-            size_compressed: table.get_or_default(1)?,
-            size_uncompressed: table.get_or_default(2)?,
+            table: offset.get::<Table>()?,
+        })
+    }
+}
+
+impl<'a> BundleView<'a> {
+    pub fn id(&self) -> Result<u64, Error> {
+        self.table.get_or_default(0)
+    }
+
+    pub fn chunks(&self) -> Result<Vector<'a, BundleChunkView<'a>>, Error> {
+        self.table.get_or_default(1)
+    }
+
+    pub fn decode(&self) -> Result<Bundle, Error> {
+        Ok(Bundle {
+            id: self.id()?,
+            chunks: self
+                .chunks()?
+                .iter()
+                .map(|item| item?.decode())
+                .collect::<Result<_, _>>()?,
         })
     }
 }
 
 impl<'a> ReadPtr<'a> for Bundle {
     const SIZE: usize = Table::SIZE;
-    fn from_ptr(offset: Ptr<'a>) -> Result<Self, String> {
-        let table = offset.get::<Table>()?;
+    fn from_ptr(offset: Ptr<'a>) -> Result<Self, Error> {
+        offset.get::<BundleView>()?.decode()
+    }
+}
+
+impl<'a> ReadPtr<'a> for LangView<'a> {
+    const SIZE: usize = Table::SIZE;
+    fn from_ptr(offset: Ptr<'a>) -> Result<Self, Error> {
         Ok(Self {
-            id: table.get_or_default(0)?,
-            chunks: table.get_or_default(1)?,
+            table: offset.get::<Table>()?,
+        })
+    }
+}
+
+impl<'a> LangView<'a> {
+    pub fn id(&self) -> Result<u8, Error> {
+        self.table.get_or_default(0)
+    }
+
+    pub fn name(&self) -> Result<&'a str, Error> {
+        self.table.get_or_default(1)
+    }
+
+    pub fn decode(&self) -> Result<Lang, Error> {
+        Ok(Lang {
+            id: self.id()?,
+            name: self.name()?.to_string(),
         })
     }
 }
 
 impl<'a> ReadPtr<'a> for Lang {
     const SIZE: usize = Table::SIZE;
-    fn from_ptr(offset: Ptr<'a>) -> Result<Self, String> {
-        let table = offset.get::<Table>()?;
+    fn from_ptr(offset: Ptr<'a>) -> Result<Self, Error> {
+        offset.get::<LangView>()?.decode()
+    }
+}
+
+impl<'a> ReadPtr<'a> for DirView<'a> {
+    const SIZE: usize = Table::SIZE;
+    fn from_ptr(offset: Ptr<'a>) -> Result<Self, Error> {
         Ok(Self {
-            id: table.get_or_default(0)?,
-            name: table.get_or_default(1)?,
+            table: offset.get::<Table>()?,
+        })
+    }
+}
+
+impl<'a> DirView<'a> {
+    pub fn id(&self) -> Result<u64, Error> {
+        self.table.get_or_default(0)
+    }
+
+    pub fn parent_id(&self) -> Result<u64, Error> {
+        self.table.get_or_default(1)
+    }
+
+    pub fn name(&self) -> Result<&'a str, Error> {
+        self.table.get_or_default(2)
+    }
+
+    pub fn decode(&self) -> Result<Dir, Error> {
+        Ok(Dir {
+            id: self.id()?,
+            parent_id: self.parent_id()?,
+            name: self.name()?.to_string(),
         })
     }
 }
 
 impl<'a> ReadPtr<'a> for Dir {
     const SIZE: usize = Table::SIZE;
-    fn from_ptr(offset: Ptr<'a>) -> Result<Self, String> {
-        let table = offset.get::<Table>()?;
+    fn from_ptr(offset: Ptr<'a>) -> Result<Self, Error> {
+        offset.get::<DirView>()?.decode()
+    }
+}
+
+impl<'a> ReadPtr<'a> for FileView<'a> {
+    const SIZE: usize = Table::SIZE;
+    fn from_ptr(offset: Ptr<'a>) -> Result<Self, Error> {
         Ok(Self {
-            id: table.get_or_default(0)?,
-            parent_id: table.get_or_default(1)?,
-            name: table.get_or_default(2)?,
+            table: offset.get::<Table>()?,
+        })
+    }
+}
+
+impl<'a> FileView<'a> {
+    pub fn id(&self) -> Result<u64, Error> {
+        self.table.get_or_default(0)
+    }
+
+    pub fn parent_id(&self) -> Result<u64, Error> {
+        self.table.get_or_default(1)
+    }
+
+    pub fn size(&self) -> Result<u32, Error> {
+        self.table.get_or_default(2)
+    }
+
+    pub fn name(&self) -> Result<&'a str, Error> {
+        self.table.get_or_default(3)
+    }
+
+    pub fn lang_flags(&self) -> Result<u64, Error> {
+        self.table.get_or_default(4)
+    }
+
+    pub fn unk5(&self) -> Result<u8, Error> {
+        self.table.get_or_default(5)
+    }
+
+    pub fn unk6(&self) -> Result<u8, Error> {
+        self.table.get_or_default(6)
+    }
+
+    pub fn chunk_ids(&self) -> Result<Vector<'a, u64>, Error> {
+        self.table.get_or_default(7)
+    }
+
+    pub fn unk8(&self) -> Result<u8, Error> {
+        self.table.get_or_default(8)
+    }
+
+    pub fn link(&self) -> Result<&'a str, Error> {
+        self.table.get_or_default(9)
+    }
+
+    pub fn unk10(&self) -> Result<u8, Error> {
+        self.table.get_or_default(10)
+    }
+
+    pub fn params_index(&self) -> Result<u8, Error> {
+        self.table.get_or_default(11)
+    }
+
+    pub fn permissions(&self) -> Result<u8, Error> {
+        self.table.get_or_default(12)
+    }
+
+    pub fn decode(&self) -> Result<File, Error> {
+        Ok(File {
+            id: self.id()?,
+            parent_id: self.parent_id()?,
+            size: self.size()?,
+            name: self.name()?.to_string(),
+            lang_flags: self.lang_flags()?,
+            unk5: self.unk5()?,
+            unk6: self.unk6()?,
+            chunk_ids: self.chunk_ids()?.iter().collect::<Result<_, _>>()?,
+            unk8: self.unk8()?,
+            link: self.link()?.to_string(),
+            unk10: self.unk10()?,
+            params_index: self.params_index()?,
+            permissions: self.permissions()?,
         })
     }
 }
 
 impl<'a> ReadPtr<'a> for File {
     const SIZE: usize = Table::SIZE;
-    fn from_ptr(offset: Ptr<'a>) -> Result<Self, String> {
-        let table = offset.get::<Table>()?;
+    fn from_ptr(offset: Ptr<'a>) -> Result<Self, Error> {
+        offset.get::<FileView>()?.decode()
+    }
+}
+
+impl<'a> ReadPtr<'a> for KeyView<'a> {
+    const SIZE: usize = Table::SIZE;
+    fn from_ptr(offset: Ptr<'a>) -> Result<Self, Error> {
         Ok(Self {
-            id: table.get_or_default(0)?,
-            parent_id: table.get_or_default(1)?,
-            size: table.get_or_default(2)?,
-            name: table.get_or_default(3)?,
-            lang_flags: table.get_or_default(4)?,
-            unk5: table.get_or_default(5)?,
-            unk6: table.get_or_default(6)?,
-            chunk_ids: table.get_or_default(7)?,
-            unk8: table.get_or_default(8)?,
-            link: table.get_or_default(9)?,
-            unk10: table.get_or_default(10)?,
-            params_index: table.get_or_default(11)?,
-            permissions: table.get_or_default(12)?,
+            table: offset.get::<Table>()?,
+        })
+    }
+}
+
+impl<'a> KeyView<'a> {
+    pub fn id(&self) -> Result<u64, Error> {
+        self.table.get_or_default(0)
+    }
+
+    pub fn data(&self) -> Result<Vector<'a, u8>, Error> {
+        self.table.get_or_default(1)
+    }
+
+    pub fn decode(&self) -> Result<Key, Error> {
+        Ok(Key {
+            id: self.id()?,
+            data: self.data()?.iter().collect::<Result<_, _>>()?,
         })
     }
 }
 
 impl<'a> ReadPtr<'a> for Key {
     const SIZE: usize = Table::SIZE;
-    fn from_ptr(offset: Ptr<'a>) -> Result<Self, String> {
-        let _table = offset.get::<Table>()?;
-        Ok(Self {})
+    fn from_ptr(offset: Ptr<'a>) -> Result<Self, Error> {
+        offset.get::<KeyView>()?.decode()
     }
 }
 
-impl<'a> ReadPtr<'a> for Params {
+impl<'a> ReadPtr<'a> for ParamsView<'a> {
     const SIZE: usize = Table::SIZE;
-    fn from_ptr(offset: Ptr<'a>) -> Result<Self, String> {
-        let table = offset.get::<Table>()?;
+    fn from_ptr(offset: Ptr<'a>) -> Result<Self, Error> {
         Ok(Self {
-            unk0: table.get_or_default(0)?,
-            hash_type: table.get_or_default(1)?,
-            unk2: table.get_or_default(2)?,
-            unk3: table.get_or_default(3)?,
-            max_uncompressed: table.get_or_default(4)?,
+            table: offset.get::<Table>()?,
         })
     }
 }
 
-impl<'a> ReadPtr<'a> for Body {
+impl<'a> ParamsView<'a> {
+    pub fn unk0(&self) -> Result<u16, Error> {
+        self.table.get_or_default(0)
+    }
+
+    pub fn hash_type(&self) -> Result<u8, Error> {
+        self.table.get_or_default(1)
+    }
+
+    pub fn unk2(&self) -> Result<u8, Error> {
+        self.table.get_or_default(2)
+    }
+
+    pub fn unk3(&self) -> Result<u32, Error> {
+        self.table.get_or_default(3)
+    }
+
+    pub fn max_uncompressed(&self) -> Result<u32, Error> {
+        self.table.get_or_default(4)
+    }
+
+    pub fn decode(&self) -> Result<Params, Error> {
+        Ok(Params {
+            unk0: self.unk0()?,
+            hash_type: self.hash_type()?,
+            unk2: self.unk2()?,
+            unk3: self.unk3()?,
+            max_uncompressed: self.max_uncompressed()?,
+        })
+    }
+}
+
+impl<'a> ReadPtr<'a> for Params {
     const SIZE: usize = Table::SIZE;
-    fn from_ptr(offset: Ptr<'a>) -> Result<Self, String> {
-        let table = offset.get::<Table>()?;
+    fn from_ptr(offset: Ptr<'a>) -> Result<Self, Error> {
+        offset.get::<ParamsView>()?.decode()
+    }
+}
+
+impl<'a> ReadPtr<'a> for BodyView<'a> {
+    const SIZE: usize = Table::SIZE;
+    fn from_ptr(offset: Ptr<'a>) -> Result<Self, Error> {
         Ok(Self {
-            bundles: table.get_or_default(0)?,
-            langs: table.get_or_default(1)?,
-            files: table.get_or_default(2)?,
-            dirs: table.get_or_default(3)?,
-            keys: table.get_or_default(4)?,
-            params: table.get_or_default(5)?,
+            table: offset.get::<Table>()?,
         })
     }
 }
 
+impl<'a> BodyView<'a> {
+    pub fn bundles(&self) -> Result<Vector<'a, BundleView<'a>>, Error> {
+        self.table.get_or_default(0)
+    }
+
+    pub fn langs(&self) -> Result<Vector<'a, LangView<'a>>, Error> {
+        self.table.get_or_default(1)
+    }
+
+    pub fn files(&self) -> Result<Vector<'a, FileView<'a>>, Error> {
+        self.table.get_or_default(2)
+    }
+
+    pub fn dirs(&self) -> Result<Vector<'a, DirView<'a>>, Error> {
+        self.table.get_or_default(3)
+    }
+
+    pub fn keys(&self) -> Result<Vector<'a, KeyView<'a>>, Error> {
+        self.table.get_or_default(4)
+    }
+
+    pub fn params(&self) -> Result<Vector<'a, ParamsView<'a>>, Error> {
+        self.table.get_or_default(5)
+    }
+
+    pub fn decode(&self) -> Result<Body, Error> {
+        Ok(Body {
+            bundles: self
+                .bundles()?
+                .iter()
+                .map(|item| item?.decode())
+                .collect::<Result<_, _>>()?,
+            langs: self
+                .langs()?
+                .iter()
+                .map(|item| item?.decode())
+                .collect::<Result<_, _>>()?,
+            files: self
+                .files()?
+                .iter()
+                .map(|item| item?.decode())
+                .collect::<Result<_, _>>()?,
+            dirs: self
+                .dirs()?
+                .iter()
+                .map(|item| item?.decode())
+                .collect::<Result<_, _>>()?,
+            keys: self
+                .keys()?
+                .iter()
+                .map(|item| item?.decode())
+                .collect::<Result<_, _>>()?,
+            params: self
+                .params()?
+                .iter()
+                .map(|item| item?.decode())
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl<'a> ReadPtr<'a> for Body {
+    const SIZE: usize = Table::SIZE;
+    fn from_ptr(offset: Ptr<'a>) -> Result<Self, Error> {
+        offset.get::<BodyView>()?.decode()
+    }
+}
+
+impl WritePtr for BundleChunk {
+    fn size(&self) -> usize {
+        Table::SIZE
+    }
+    fn to_ptr(&self, builder: &mut Builder, at: usize) {
+        builder.write_table(
+            at,
+            &[&self.id, &self.size_compressed, &self.size_uncompressed],
+        );
+    }
+}
+
+impl WritePtr for Bundle {
+    fn size(&self) -> usize {
+        Table::SIZE
+    }
+    fn to_ptr(&self, builder: &mut Builder, at: usize) {
+        builder.write_table(at, &[&self.id, &self.chunks]);
+    }
+}
+
+impl WritePtr for Lang {
+    fn size(&self) -> usize {
+        Table::SIZE
+    }
+    fn to_ptr(&self, builder: &mut Builder, at: usize) {
+        builder.write_table(at, &[&self.id, &self.name]);
+    }
+}
+
+impl WritePtr for Dir {
+    fn size(&self) -> usize {
+        Table::SIZE
+    }
+    fn to_ptr(&self, builder: &mut Builder, at: usize) {
+        builder.write_table(at, &[&self.id, &self.parent_id, &self.name]);
+    }
+}
+
+impl WritePtr for File {
+    fn size(&self) -> usize {
+        Table::SIZE
+    }
+    fn to_ptr(&self, builder: &mut Builder, at: usize) {
+        builder.write_table(
+            at,
+            &[
+                &self.id,
+                &self.parent_id,
+                &self.size,
+                &self.name,
+                &self.lang_flags,
+                &self.unk5,
+                &self.unk6,
+                &self.chunk_ids,
+                &self.unk8,
+                &self.link,
+                &self.unk10,
+                &self.params_index,
+                &self.permissions,
+            ],
+        );
+    }
+}
+
+impl WritePtr for Key {
+    fn size(&self) -> usize {
+        Table::SIZE
+    }
+    fn to_ptr(&self, builder: &mut Builder, at: usize) {
+        builder.write_table(at, &[&self.id, &self.data]);
+    }
+}
+
+impl WritePtr for Params {
+    fn size(&self) -> usize {
+        Table::SIZE
+    }
+    fn to_ptr(&self, builder: &mut Builder, at: usize) {
+        builder.write_table(
+            at,
+            &[
+                &self.unk0,
+                &self.hash_type,
+                &self.unk2,
+                &self.unk3,
+                &self.max_uncompressed,
+            ],
+        );
+    }
+}
+
+impl WritePtr for Body {
+    fn size(&self) -> usize {
+        Table::SIZE
+    }
+    fn to_ptr(&self, builder: &mut Builder, at: usize) {
+        builder.write_table(
+            at,
+            &[
+                &self.bundles,
+                &self.langs,
+                &self.files,
+                &self.dirs,
+                &self.keys,
+                &self.params,
+            ],
+        );
+    }
+}
+
 impl Header {
     pub const SIZE: u32 = 28;
-    fn read<R: io::Read>(reader: &mut R) -> Result<Self, io::Error> {
-        let mut magic = [0; 4];
-        let mut version = [0; 2];
-        let mut flags = [0; 2];
-        let mut offset = [0; 4];
-        let mut size_compressed = [0; 4];
-        let mut checksum = [0; 8];
-        let mut size_uncompressed = [0; 4];
-        reader.read(&mut magic)?;
-        reader.read(&mut version)?;
-        reader.read(&mut flags)?;
-        reader.read(&mut offset)?;
-        reader.read(&mut size_compressed)?;
-        reader.read(&mut checksum)?;
-        reader.read(&mut size_uncompressed)?;
+    pub const MAGIC: [u8; 4] = *b"RMAN";
+    pub const VERSION_MAJOR: u8 = 2;
+    pub const VERSION_MINOR_MAX: u8 = 1;
+    pub const FLAG_COMPRESSED: u16 = 1 << 9;
+
+    fn read_bytes<R: io::Read, const N: usize>(reader: &mut R) -> Result<[u8; N], Error> {
+        let mut buffer = [0u8; N];
+        match reader.read_exact(&mut buffer) {
+            Ok(()) => Ok(buffer),
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                throw(Error::parse("Truncated header!"))
+            }
+            Err(err) => throw(Error::io("Failed to read header!", err)),
+        }
+    }
+
+    fn read<R: io::Read>(reader: &mut R) -> Result<Self, Error> {
+        let magic = Self::read_bytes::<R, 4>(reader)?;
+        if magic != Self::MAGIC {
+            return throw(Error::parse("Not an RMAN file: bad magic!"));
+        }
+        let version = Self::read_bytes::<R, 2>(reader)?;
+        if version[0] != Self::VERSION_MAJOR {
+            return throw(Error::parse(format!(
+                "Unsupported major version: {}!",
+                version[0]
+            )));
+        }
+        if version[1] > Self::VERSION_MINOR_MAX {
+            return throw(Error::parse(format!(
+                "Unsupported minor version: {}!",
+                version[1]
+            )));
+        }
+        let flags = u16::from_le_bytes(Self::read_bytes(reader)?);
+        if flags & Self::FLAG_COMPRESSED == 0 {
+            return throw(Error::parse("Body is not compressed!"));
+        }
+        let offset = u32::from_le_bytes(Self::read_bytes(reader)?);
+        let size_compressed = u32::from_le_bytes(Self::read_bytes(reader)?);
+        let checksum = u64::from_le_bytes(Self::read_bytes(reader)?);
+        let size_uncompressed = u32::from_le_bytes(Self::read_bytes(reader)?);
+        if offset < Self::SIZE {
+            return throw(Error::parse("Body offset at bad position!").with_offset(offset as u64));
+        }
+        let skip = (offset - Self::SIZE) as u64;
+        let skipped = io::copy(&mut io::Read::take(reader, skip), &mut io::sink())
+            .map_err(|err| Error::io("Failed to skip to body!", err))?;
+        if skipped != skip {
+            return throw(Error::parse("Truncated header!"));
+        }
         Ok(Header {
             magic,
             version,
-            flags: u16::from_le_bytes(flags),
-            offset: u32::from_le_bytes(offset),
-            size_compressed: u32::from_le_bytes(size_compressed),
-            checksum: u64::from_le_bytes(checksum),
-            size_uncompressed: u32::from_le_bytes(size_uncompressed),
+            flags,
+            offset,
+            size_compressed,
+            checksum,
+            size_uncompressed,
         })
     }
+
+    fn write<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+        let mut buffer = Vec::with_capacity(Self::SIZE as usize);
+        buffer.extend_from_slice(&self.magic);
+        buffer.extend_from_slice(&self.version);
+        buffer.extend_from_slice(&self.flags.to_le_bytes());
+        buffer.extend_from_slice(&self.offset.to_le_bytes());
+        buffer.extend_from_slice(&self.size_compressed.to_le_bytes());
+        buffer.extend_from_slice(&self.checksum.to_le_bytes());
+        buffer.extend_from_slice(&self.size_uncompressed.to_le_bytes());
+        writer
+            .write_all(&buffer)
+            .map_err(|err| Error::io("Failed to write header!", err))
+    }
 }
 
-impl Manifest {
-    fn verify_filename(name: &str) -> Result<(), String> {
-        if name == "." || name == ".." {
-            throw("Name can not be . or ..!")
-        } else {
-            for c in name.chars() {
-                if c.is_alphanumeric() {
-                    continue;
-                }
-                if c == '.' || c == ' ' || c == '+' || c == '-' || c == '_' {
-                    continue;
-                }
-                return throw("Illegal character in name!");
+impl Body {
+    pub fn write<W: io::Write>(&self, writer: &mut W, id: u64) -> Result<(), Error> {
+        let data = Builder::finish(self);
+        let data_compressed = zstd::block::compress(&data, zstd::DEFAULT_COMPRESSION_LEVEL)
+            .map_err(|err| Error::compression("Failed to compress data!", err))?;
+        if data.len() > u32::MAX as usize || data_compressed.len() > u32::MAX as usize {
+            return throw(Error::validation("Body would go out of 4GB boundary!"));
+        }
+        let header = Header {
+            magic: Header::MAGIC,
+            version: [Header::VERSION_MAJOR, 0],
+            flags: Header::FLAG_COMPRESSED,
+            offset: Header::SIZE,
+            size_compressed: data_compressed.len() as u32,
+            checksum: id,
+            size_uncompressed: data.len() as u32,
+        };
+        header.write(writer)?;
+        writer
+            .write_all(&data_compressed)
+            .map_err(|err| Error::io("Failed to write compressed!", err))
+    }
+}
+
+impl Integrity {
+    const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+
+    fn read_frame_header(header: &Header, data: &[u8]) -> Result<Self, Error> {
+        if data.len() < 6 || data[..4] != Self::ZSTD_MAGIC {
+            return throw(Error::parse("Body is not a zstd frame!"));
+        }
+        let descriptor = data[4];
+        let fcs_flag = descriptor >> 6;
+        let single_segment = descriptor & 0x20 != 0;
+        let checked_content_checksum = descriptor & 0x04 != 0;
+        let dict_id_size = [0usize, 1, 2, 4][(descriptor & 0x03) as usize];
+        let fcs_size = match fcs_flag {
+            0 if single_segment => 1,
+            0 => 0,
+            1 => 2,
+            2 => 4,
+            _ => 8,
+        };
+        let fcs_start = 5 + (!single_segment as usize) + dict_id_size;
+        let fcs_data = match data.get(fcs_start..fcs_start + fcs_size) {
+            Some(fcs_data) => fcs_data,
+            None => return throw(Error::parse("Truncated zstd frame header!")),
+        };
+        let mut buffer = [0u8; 8];
+        buffer[..fcs_size].copy_from_slice(fcs_data);
+        let mut content_size = u64::from_le_bytes(buffer);
+        if fcs_size == 2 {
+            content_size += 256;
+        }
+        let checked_content_size = fcs_size != 0;
+        if checked_content_size && content_size != header.size_uncompressed as u64 {
+            return throw(Error::integrity(
+                "Frame content size does not match uncompressed size!",
+            ));
+        }
+        Ok(Self {
+            size_compressed: header.size_compressed,
+            size_uncompressed: header.size_uncompressed,
+            checked_content_size,
+            checked_content_checksum,
+        })
+    }
+
+    fn decompress(header: &Header, data: &[u8]) -> Result<(Vec<u8>, Self), Error> {
+        let integrity = Self::read_frame_header(header, data)?;
+        let mut decoder = zstd::stream::read::Decoder::with_buffer(data)
+            .map_err(|err| Error::decompression("Failed to create decoder!", err))?
+            .single_frame();
+        let limit = header.size_uncompressed as u64;
+        let mut result = Vec::with_capacity(header.size_uncompressed as usize);
+        decoder
+            .by_ref()
+            .take(limit + 1)
+            .read_to_end(&mut result)
+            .map_err(|err| Error::decompression("Failed to read data!", err))?;
+        if result.len() as u64 != limit {
+            return throw(Error::integrity(
+                "Decompressed size does not match uncompressed size!",
+            ));
+        }
+        if !decoder.finish().is_empty() {
+            return throw(Error::integrity("Trailing data after compressed body!"));
+        }
+        Ok((result, integrity))
+    }
+}
+
+pub fn verify_filename(name: &str) -> Result<(), Error> {
+    if name == "." || name == ".." {
+        throw(Error::validation("Name can not be . or ..!").with_path(name))
+    } else {
+        for c in name.chars() {
+            if c.is_alphanumeric() {
+                continue;
+            }
+            if c == '.' || c == ' ' || c == '+' || c == '-' || c == '_' {
+                continue;
             }
-            Ok(())
-        }
-    }
-
-    pub fn read<R: io::Read>(reader: &mut R) -> Result<Self, String> {
-        let header = re_throw(Header::read(reader), "Failed to read header")?;
-        if header.offset < Header::SIZE {
-            throw("Body offset at bad position!")?;
-        }
-        for _ in header.offset..Header::SIZE {
-            let mut one = [0u8; 1];
-            re_throw(reader.read(&mut one), "Failed to skip")?;
-        }
-        let mut data_compressed = Vec::new();
-        data_compressed.resize(header.size_compressed as usize, 0u8);
-        re_throw(
-            reader.read_exact(&mut data_compressed),
-            "Failed to read compressed",
-        )?;
-        let data = re_throw(
-            zstd::stream::decode_all(io::Cursor::new(&data_compressed)),
-            "Failed to read data",
-        )?;
-        let body = Ptr::new(&data, 0)?.get::<Body>()?;
+            return throw(Error::validation("Illegal character in name!").with_path(name));
+        }
+        Ok(())
+    }
+}
+
+pub fn verify_bundle_id(bundle_id: u64) -> Result<(), Error> {
+    if bundle_id == 0 {
+        throw(Error::validation("Bundle id can not be 0!"))
+    } else {
+        Ok(())
+    }
+}
+
+pub fn verify_chunk_id(bundle_id: u64, chunk_id: u64) -> Result<(), Error> {
+    if chunk_id == 0 {
+        throw(Error::validation("Chunk id can not be 0!").with_bundle(bundle_id))
+    } else {
+        Ok(())
+    }
+}
+
+pub fn verify_file_id(file_id: u64) -> Result<(), Error> {
+    if file_id == 0 {
+        throw(Error::validation("File id can not be 0!"))
+    } else {
+        Ok(())
+    }
+}
+
+pub fn verify_params(params: &Params) -> Result<(), Error> {
+    if params.max_uncompressed > CHUNK_LIMIT {
+        throw(Error::validation(
+            "Chunk params go over uncompressed CHUNK_LIMIT!",
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+impl Index {
+    fn new(body: &BodyView, limits: &ParseLimits) -> Result<Self, Error> {
+        let files = body.files()?;
+        if files.len() > limits.max_files {
+            return throw(Error::validation(format!(
+                "File count {} exceeds limit {}!",
+                files.len(),
+                limits.max_files
+            )));
+        }
         let mut chunks = HashMap::new();
-        for bundle in body.bundles.iter() {
-            if bundle.id == 0 {
-                throw("Bundle id can not be 0!")?;
+        let mut chunk_count = 0usize;
+        for bundle in body.bundles()?.iter() {
+            let bundle = bundle?;
+            let bundle_id = bundle.id()?;
+            verify_bundle_id(bundle_id)?;
+            let bundle_chunks = bundle.chunks()?;
+            chunk_count += bundle_chunks.len();
+            if chunk_count > limits.max_chunks {
+                return throw(Error::validation(format!(
+                    "Chunk count exceeds limit {}!",
+                    limits.max_chunks
+                )));
             }
             let mut offset_compressed = 0u64;
-            for chunk in bundle.chunks.iter() {
-                if chunk.id == 0 {
-                    throw("Chunk id can not be 0!")?;
-                }
+            for chunk in bundle_chunks.iter() {
+                let chunk = chunk?.decode()?;
+                verify_chunk_id(bundle_id, chunk.id)?;
                 chunks.insert(
                     chunk.id,
                     Chunk {
                         chunk_id: chunk.id,
-                        bundle_id: bundle.id,
+                        bundle_id,
                         size_compressed: chunk.size_compressed,
                         size_uncompressed: chunk.size_uncompressed,
-                        offset_compressed: offset_compressed as u32,
+                        offset_compressed,
                         offset_uncompressed: 0,
                     },
                 );
                 offset_compressed += chunk.size_compressed as u64;
-                if offset_compressed > u32::MAX as u64 {
-                    throw("Compressed offset would go out of 4GB boundary!")?;
-                }
             }
         }
         let mut langs = HashMap::new();
-        for lang in body.langs {
-            Self::verify_filename(&lang.name)?;
+        for lang in body.langs()?.iter() {
+            let lang = lang?.decode()?;
+            verify_filename(&lang.name)?;
             langs.insert(lang.id, lang);
         }
         let mut dirs = HashMap::new();
-        for dir in body.dirs {
-            Self::verify_filename(&dir.name)?;
+        for dir in body.dirs()?.iter() {
+            let dir = dir?.decode()?;
+            verify_filename(&dir.name)?;
             dirs.insert(dir.id, dir);
         }
         let mut params = Vec::new();
-        for param in body.params {
-            if param.max_uncompressed > CHUNK_LIMIT {
-                throw("Chunk params go over uncompressed CHUNK_LIMIT!")?;
-            }
+        for param in body.params()?.iter() {
+            let param = param?.decode()?;
+            verify_params(&param)?;
             params.push(param);
         }
-        for file in body.files.iter() {
-            if file.id == 0 {
-                throw("File id can not be 0!")?;
-            }
-            Self::verify_filename(&file.name)?;
+        for file in files.iter() {
+            let file = file?;
+            verify_file_id(file.id()?)?;
+            verify_filename(file.name()?)?;
         }
         Ok(Self {
-            id: header.checksum,
-            files: body.files,
             chunks,
             langs,
             dirs,
             params,
+            limits: *limits,
         })
     }
 
-    pub fn get_file_name(&self, name: &str, parent_id: u64) -> Result<String, String> {
-        let mut name = name.to_string();
-        let org_parent_id = parent_id;
-        let mut parent_id = parent_id;
-        loop {
-            if let Some(dir) = self.dirs.get(&parent_id) {
-                if dir.name == "" {
-                    break;
+    pub fn get_dirs(&self, problems: &mut Vec<String>) -> Result<HashMap<u64, super::Dir>, Error> {
+        let mut results = HashMap::<u64, super::Dir>::new();
+        let mut roots = self
+            .dirs
+            .values()
+            .filter(|dir| dir.name.is_empty())
+            .map(|dir| dir.id)
+            .collect::<Vec<_>>();
+        roots.sort_unstable();
+        if let Some(&root) = roots.first() {
+            for &id in &roots[1..] {
+                problems.push(format!("Extra root directory {:016X}!", id));
+            }
+            results.insert(
+                root,
+                super::Dir {
+                    id: root,
+                    parent_id: None,
+                    name: String::new(),
+                    path: String::new(),
+                    dirs: Vec::new(),
+                },
+            );
+        }
+        let mut ids = self.dirs.keys().copied().collect::<Vec<_>>();
+        ids.sort_unstable();
+        'dirs: for id in ids {
+            if roots.contains(&id) {
+                continue;
+            }
+            let mut chain = Vec::new();
+            let mut visited = HashSet::new();
+            let mut parent_id = id;
+            let mut path = loop {
+                if let Some(dir) = results.get(&parent_id) {
+                    break dir.path.clone();
                 }
-                name = format!("{}/{}", dir.name, name);
+                let dir = match self.dirs.get(&parent_id) {
+                    Some(dir) if !roots.contains(&dir.id) => dir,
+                    _ => {
+                        problems.push(format!(
+                            "Orphan directory {:016X} {}!",
+                            id, &self.dirs[&id].name
+                        ));
+                        continue 'dirs;
+                    }
+                };
+                if !visited.insert(dir.id) {
+                    problems.push(format!(
+                        "Directory cycle at {:016X} {}!",
+                        id, &self.dirs[&id].name
+                    ));
+                    continue 'dirs;
+                }
+                chain.push(dir);
                 parent_id = dir.parent_id;
-                if parent_id == org_parent_id {
-                    throw("Directory cycle detected!")?;
+            };
+            for dir in chain.into_iter().rev() {
+                path = if path.is_empty() {
+                    dir.name.clone()
+                } else {
+                    format!("{}/{}", path, dir.name)
+                };
+                if path.split('/').count() > self.limits.max_dir_depth {
+                    return throw(
+                        Error::validation(format!(
+                            "Directory depth exceeds limit {}!",
+                            self.limits.max_dir_depth
+                        ))
+                        .with_path(path),
+                    );
                 }
-            } else {
-                throw("Failed to find dir by id!")?;
+                results.insert(
+                    dir.id,
+                    super::Dir {
+                        id: dir.id,
+                        parent_id: Some(dir.parent_id),
+                        name: dir.name.clone(),
+                        path: path.clone(),
+                        dirs: Vec::new(),
+                    },
+                );
+            }
+        }
+        let mut children = results
+            .values()
+            .filter_map(|dir| Some((dir.parent_id?, dir.name.clone(), dir.id)))
+            .collect::<Vec<_>>();
+        children.sort();
+        for (parent_id, _, id) in children {
+            if let Some(parent) = results.get_mut(&parent_id) {
+                parent.dirs.push(id);
             }
         }
-        Ok(name)
+        Ok(results)
     }
 
-    pub fn get_langs(&self, lang_flags: u64) -> Result<HashSet<String>, String> {
-        let mut langs = HashSet::new();
-        for i in 0..32 {
-            if lang_flags & (1 << i) != 0 {
-                if let Some(lang) = self.langs.get(&(i + 1)) {
-                    langs.insert(lang.name.to_lowercase());
-                } else {
-                    throw("Failed to find lang by id!")?;
-                }
+    pub fn get_lang_list(&self) -> Vec<super::Lang> {
+        let mut langs = self
+            .langs
+            .values()
+            .filter(|lang| super::LangSet::from_id(lang.id).is_some())
+            .map(|lang| super::Lang {
+                id: lang.id,
+                name: lang.name.clone(),
+            })
+            .collect::<Vec<_>>();
+        langs.sort_by_key(|lang| lang.id);
+        langs
+    }
+
+    pub fn get_langs(&self, lang_flags: u64) -> Result<super::LangSet, Error> {
+        let langs = super::LangSet { flags: lang_flags };
+        for id in langs.ids() {
+            if !self.langs.contains_key(&id) {
+                throw(Error::validation(format!(
+                    "Failed to find lang by id {}!",
+                    id
+                )))?;
             }
         }
-        if langs.len() == 0 {
-            langs.insert("none".to_string());
-        }
         Ok(langs)
     }
 
-    pub fn get_chunk(&self, chunk_id: u64) -> Result<Chunk, String> {
+    pub fn get_chunk(&self, chunk_id: u64) -> Result<Chunk, Error> {
         if let Some(&chunk_data) = self.chunks.get(&chunk_id) {
             Ok(chunk_data)
         } else {
-            throw("Failed to find chunk bundle by id!")
+            throw(Error::validation("Failed to find chunk bundle by id!").with_chunk(chunk_id))
         }
     }
 
-    pub fn get_chunks(&self, chunk_ids: &[u64]) -> Result<Vec<Chunk>, String> {
+    pub fn get_chunks<I: IntoIterator<Item = Result<u64, Error>>>(
+        &self,
+        chunk_ids: I,
+    ) -> Result<Vec<Chunk>, Error> {
         let mut offset_uncompressed = 0u64;
         let mut results = Vec::new();
         for chunk_id in chunk_ids {
-            let mut chunk = self.get_chunk(*chunk_id)?;
-            chunk.offset_uncompressed = offset_uncompressed as u32;
+            let mut chunk = self.get_chunk(chunk_id?)?;
+            chunk.offset_uncompressed = offset_uncompressed;
             results.push(chunk);
             offset_uncompressed += chunk.size_uncompressed as u64;
-            if offset_uncompressed > u32::MAX as u64 {
-                throw("Uncompressed offset would go out of 4GB boundary!")?;
-            }
         }
         Ok(results)
     }
 
-    pub fn get_params(&self, params_id: u8) -> Result<Params, String> {
+    pub fn get_params(&self, params_id: u8) -> Result<Params, Error> {
         if let Some(&params) = self.params.get(params_id as usize) {
             Ok(params)
         } else {
-            throw("Failed to find chunk bundle by id!")
+            throw(Error::validation(format!(
+                "Failed to find params by id {}!",
+                params_id
+            )))
         }
     }
 }
+
+impl ManifestData {
+    pub fn read<R: io::Read>(reader: &mut R, limits: &ParseLimits) -> Result<Self, Error> {
+        let header = Header::read(reader)?;
+        if header.size_compressed > limits.max_compressed {
+            return throw(Error::validation(format!(
+                "Compressed size {} exceeds limit {}!",
+                header.size_compressed, limits.max_compressed
+            )));
+        }
+        if header.size_uncompressed > limits.max_decompressed {
+            return throw(Error::validation(format!(
+                "Decompressed size {} exceeds limit {}!",
+                header.size_uncompressed, limits.max_decompressed
+            )));
+        }
+        let mut data_compressed = vec![0; header.size_compressed as usize];
+        reader
+            .read_exact(&mut data_compressed)
+            .map_err(|err| Error::io("Failed to read compressed!", err))?;
+        let (data, integrity) = Integrity::decompress(&header, &data_compressed)?;
+        Ok(Self {
+            id: header.checksum,
+            integrity,
+            data,
+            limits: *limits,
+        })
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn view(&self) -> Result<ManifestView<'_>, Error> {
+        let body = Ptr::with_limits(&self.data, 0, &self.limits)?.get::<BodyView>()?;
+        Ok(ManifestView {
+            id: self.id,
+            integrity: self.integrity,
+            files: body.files()?,
+            keys: body.keys(),
+            index: Index::new(&body, &self.limits)?,
+        })
+    }
+}
+
+impl<'a> ManifestView<'a> {
+    pub fn decode(self) -> Result<Manifest, Error> {
+        Ok(Manifest {
+            id: self.id,
+            integrity: self.integrity,
+            files: self
+                .files
+                .iter()
+                .map(|file| file?.decode())
+                .collect::<Result<_, _>>()?,
+            keys: self
+                .keys?
+                .iter()
+                .map(|key| key?.decode())
+                .collect::<Result<_, _>>()?,
+            index: self.index,
+        })
+    }
+}
+
+impl Manifest {
+    pub fn read<R: io::Read>(reader: &mut R, limits: &ParseLimits) -> Result<Self, Error> {
+        ManifestData::read(reader, limits)?.view()?.decode()
+    }
+
+    pub fn to_body(&self) -> Body {
+        let mut bundles = BTreeMap::new();
+        for chunk in self.index.chunks.values() {
+            bundles
+                .entry(chunk.bundle_id)
+                .or_insert_with(BTreeMap::new)
+                .insert(
+                    chunk.offset_compressed,
+                    BundleChunk {
+                        id: chunk.chunk_id,
+                        size_compressed: chunk.size_compressed,
+                        size_uncompressed: chunk.size_uncompressed,
+                    },
+                );
+        }
+        let mut langs = self.index.langs.values().cloned().collect::<Vec<_>>();
+        langs.sort_by_key(|lang| lang.id);
+        let mut dirs = self.index.dirs.values().cloned().collect::<Vec<_>>();
+        dirs.sort_by_key(|dir| dir.id);
+        Body {
+            bundles: bundles
+                .into_iter()
+                .map(|(id, chunks)| Bundle {
+                    id,
+                    chunks: chunks.into_values().collect(),
+                })
+                .collect(),
+            langs,
+            files: self.files.clone(),
+            dirs,
+            keys: self.keys.clone(),
+            params: self.index.params.clone(),
+        }
+    }
+
+    pub fn write<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+        self.to_body().write(writer, self.id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_body() -> Body {
+        Body {
+            bundles: vec![
+                Bundle {
+                    id: 0xB1,
+                    chunks: vec![
+                        BundleChunk {
+                            id: 0xC1,
+                            size_compressed: 10,
+                            size_uncompressed: 100,
+                        },
+                        BundleChunk {
+                            id: 0xC2,
+                            size_compressed: 20,
+                            size_uncompressed: 200,
+                        },
+                    ],
+                },
+                Bundle {
+                    id: 0xB2,
+                    chunks: Vec::new(),
+                },
+            ],
+            langs: vec![
+                Lang {
+                    id: 1,
+                    name: "en_US".to_string(),
+                },
+                Lang {
+                    id: 2,
+                    name: "ja_JP".to_string(),
+                },
+            ],
+            files: vec![
+                File {
+                    id: 1,
+                    parent_id: 5,
+                    size: 300,
+                    name: "a.bin".to_string(),
+                    lang_flags: 2,
+                    unk5: 1,
+                    unk6: 2,
+                    unk8: 3,
+                    chunk_ids: vec![0xC1, 0xC2],
+                    unk10: 4,
+                    params_index: 1,
+                    permissions: 7,
+                    ..Default::default()
+                },
+                File {
+                    id: 2,
+                    parent_id: 0,
+                    name: "b.lnk".to_string(),
+                    link: "data/a.bin".to_string(),
+                    ..Default::default()
+                },
+            ],
+            dirs: vec![
+                Dir {
+                    id: 0,
+                    parent_id: 0,
+                    name: String::new(),
+                },
+                Dir {
+                    id: 5,
+                    parent_id: 0,
+                    name: "data".to_string(),
+                },
+            ],
+            keys: vec![
+                Key {
+                    id: 0xB1,
+                    data: vec![1, 2, 3],
+                },
+                Key {
+                    id: 0xB2,
+                    data: Vec::new(),
+                },
+            ],
+            params: vec![
+                Params {
+                    hash_type: 2,
+                    max_uncompressed: 1000,
+                    ..Default::default()
+                },
+                Params {
+                    unk0: 1,
+                    hash_type: 3,
+                    unk2: 2,
+                    unk3: 9,
+                    max_uncompressed: 200,
+                },
+            ],
+        }
+    }
+
+    fn assert_body_eq(actual: &Body, expected: &Body) {
+        assert_eq!(actual.bundles.len(), expected.bundles.len());
+        for (actual, expected) in actual.bundles.iter().zip(&expected.bundles) {
+            assert_eq!(actual.id, expected.id);
+            assert_eq!(actual.chunks.len(), expected.chunks.len());
+            for (actual, expected) in actual.chunks.iter().zip(&expected.chunks) {
+                assert_eq!(actual.id, expected.id);
+                assert_eq!(actual.size_compressed, expected.size_compressed);
+                assert_eq!(actual.size_uncompressed, expected.size_uncompressed);
+            }
+        }
+        assert_eq!(actual.langs.len(), expected.langs.len());
+        for (actual, expected) in actual.langs.iter().zip(&expected.langs) {
+            assert_eq!(actual.id, expected.id);
+            assert_eq!(actual.name, expected.name);
+        }
+        assert_eq!(actual.files.len(), expected.files.len());
+        for (actual, expected) in actual.files.iter().zip(&expected.files) {
+            assert_eq!(actual.id, expected.id);
+            assert_eq!(actual.parent_id, expected.parent_id);
+            assert_eq!(actual.size, expected.size);
+            assert_eq!(actual.name, expected.name);
+            assert_eq!(actual.lang_flags, expected.lang_flags);
+            assert_eq!(actual.unk5, expected.unk5);
+            assert_eq!(actual.unk6, expected.unk6);
+            assert_eq!(actual.link, expected.link);
+            assert_eq!(actual.unk8, expected.unk8);
+            assert_eq!(actual.chunk_ids, expected.chunk_ids);
+            assert_eq!(actual.unk10, expected.unk10);
+            assert_eq!(actual.params_index, expected.params_index);
+            assert_eq!(actual.permissions, expected.permissions);
+        }
+        assert_eq!(actual.dirs.len(), expected.dirs.len());
+        for (actual, expected) in actual.dirs.iter().zip(&expected.dirs) {
+            assert_eq!(actual.id, expected.id);
+            assert_eq!(actual.parent_id, expected.parent_id);
+            assert_eq!(actual.name, expected.name);
+        }
+        assert_eq!(actual.keys.len(), expected.keys.len());
+        for (actual, expected) in actual.keys.iter().zip(&expected.keys) {
+            assert_eq!(actual.id, expected.id);
+            assert_eq!(actual.data, expected.data);
+        }
+        assert_eq!(actual.params, expected.params);
+    }
+
+    #[test]
+    fn body_roundtrip() {
+        let body = sample_body();
+        let data = Builder::finish(&body);
+        let view = Ptr::new(&data, 0).unwrap().get::<BodyView>().unwrap();
+        assert_body_eq(&view.decode().unwrap(), &body);
+    }
+
+    #[test]
+    fn empty_body_roundtrip() {
+        let body = Body::default();
+        let data = Builder::finish(&body);
+        let view = Ptr::new(&data, 0).unwrap().get::<BodyView>().unwrap();
+        assert_body_eq(&view.decode().unwrap(), &body);
+    }
+
+    #[test]
+    fn manifest_roundtrip() {
+        let body = sample_body();
+        let mut data = Vec::new();
+        body.write(&mut data, 0x1234).unwrap();
+        let manifest = ManifestData::read(&mut data.as_slice(), &ParseLimits::DEFAULT).unwrap();
+        let view = manifest.view().unwrap();
+        assert_eq!(view.id, 0x1234);
+        let actual = Ptr::new(manifest.data(), 0)
+            .unwrap()
+            .get::<BodyView>()
+            .unwrap()
+            .decode()
+            .unwrap();
+        assert_body_eq(&actual, &body);
+        let decoded = view.decode().unwrap();
+        assert_eq!(decoded.files.len(), body.files.len());
+        assert_eq!(decoded.keys.len(), body.keys.len());
+        assert_eq!(decoded.index.chunks[&0xC2].offset_compressed, 10);
+    }
+}
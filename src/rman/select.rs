@@ -0,0 +1,347 @@
+use super::{
+    DownloadFile, DownloadPlan, Error, File, LangFilter, Manifest, ScanReport, UpdatePlan,
+    VerifyCache, VerifyReport,
+};
+use rayon::prelude::*;
+use std::{
+    collections::HashMap,
+    fs,
+    ops::{Bound, RangeBounds},
+};
+
+#[derive(Clone, Debug)]
+pub struct FileIndex<'a> {
+    by_id: HashMap<u64, &'a File>,
+    by_path: HashMap<&'a str, &'a File>,
+    by_path_lower: HashMap<String, Vec<&'a File>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Selector {
+    globs: Vec<glob::Pattern>,
+    regexes: Vec<regex::Regex>,
+    extensions: Vec<String>,
+    min_size: Bound<u64>,
+    max_size: Bound<u64>,
+    langs: LangFilter,
+    ignore_case: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct Selection<'a> {
+    pub manifest: &'a Manifest,
+    pub files: Vec<&'a File>,
+}
+
+impl<'a> FileIndex<'a> {
+    pub const MAX_LINK_DEPTH: usize = 40;
+
+    pub fn new(manifest: &'a Manifest) -> Self {
+        let mut by_id = HashMap::with_capacity(manifest.files.len());
+        let mut by_path = HashMap::with_capacity(manifest.files.len());
+        let mut by_path_lower = HashMap::<_, Vec<_>>::with_capacity(manifest.files.len());
+        for file in &manifest.files {
+            by_id.insert(file.id, file);
+            by_path.insert(file.name.as_str(), file);
+            by_path_lower
+                .entry(file.name.to_lowercase())
+                .or_default()
+                .push(file);
+        }
+        Self {
+            by_id,
+            by_path,
+            by_path_lower,
+        }
+    }
+
+    pub fn get_by_id(&self, id: u64) -> Option<&'a File> {
+        self.by_id.get(&id).copied()
+    }
+
+    pub fn get_by_path(&self, path: &str) -> Option<&'a File> {
+        self.resolve(self.get_by_path_nofollow(path)?)
+    }
+
+    pub fn get_by_path_nofollow(&self, path: &str) -> Option<&'a File> {
+        self.by_path.get(path.trim_start_matches('/')).copied()
+    }
+
+    pub fn get_by_path_nocase(&self, path: &str) -> Vec<&'a File> {
+        self.by_path_lower
+            .get(&path.trim_start_matches('/').to_lowercase())
+            .map_or(&[][..], |files| files.as_slice())
+            .iter()
+            .filter_map(|&file| self.resolve(file))
+            .collect()
+    }
+
+    pub fn resolve(&self, file: &'a File) -> Option<&'a File> {
+        let mut file = file;
+        for _ in 0..Self::MAX_LINK_DEPTH {
+            if !file.is_link() {
+                return Some(file);
+            }
+            file = self.get_by_path_nofollow(&file.get_link_target().ok()?)?;
+        }
+        None
+    }
+}
+
+impl Default for Selector {
+    fn default() -> Self {
+        Self {
+            globs: Vec::new(),
+            regexes: Vec::new(),
+            extensions: Vec::new(),
+            min_size: Bound::Unbounded,
+            max_size: Bound::Unbounded,
+            langs: LangFilter::ALL,
+            ignore_case: false,
+        }
+    }
+}
+
+impl Selector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn glob(mut self, pattern: &str) -> Result<Self, Error> {
+        let pattern = glob::Pattern::new(pattern).map_err(|err| {
+            Error::validation(format!("Bad glob pattern {:?}: {}!", pattern, err))
+        })?;
+        self.globs.push(pattern);
+        Ok(self)
+    }
+
+    pub fn regex(mut self, pattern: &str) -> Result<Self, Error> {
+        let regex = regex::RegexBuilder::new(pattern)
+            .case_insensitive(self.ignore_case)
+            .build()
+            .map_err(|err| Error::validation(format!("Bad regex {:?}: {}!", pattern, err)))?;
+        self.regexes.push(regex);
+        Ok(self)
+    }
+
+    pub fn extension(mut self, extension: &str) -> Self {
+        self.extensions
+            .push(extension.trim_start_matches('.').to_lowercase());
+        self
+    }
+
+    pub fn size<R: RangeBounds<u64>>(mut self, range: R) -> Self {
+        self.min_size = range.start_bound().cloned();
+        self.max_size = range.end_bound().cloned();
+        self
+    }
+
+    pub fn langs(mut self, langs: LangFilter) -> Self {
+        self.langs = langs;
+        self
+    }
+
+    pub fn ignore_case(mut self, ignore_case: bool) -> Self {
+        self.ignore_case = ignore_case;
+        for regex in &mut self.regexes {
+            if let Ok(rebuilt) = regex::RegexBuilder::new(regex.as_str())
+                .case_insensitive(ignore_case)
+                .build()
+            {
+                *regex = rebuilt;
+            }
+        }
+        self
+    }
+
+    fn matches_name(&self, name: &str) -> bool {
+        if self.globs.is_empty() && self.regexes.is_empty() {
+            return true;
+        }
+        let options = glob::MatchOptions {
+            case_sensitive: !self.ignore_case,
+            require_literal_separator: true,
+            require_literal_leading_dot: false,
+        };
+        self.globs
+            .iter()
+            .any(|pattern| pattern.matches_with(name, options))
+            || self.regexes.iter().any(|regex| regex.is_match(name))
+    }
+
+    fn matches_extension(&self, name: &str) -> bool {
+        if self.extensions.is_empty() {
+            return true;
+        }
+        let file_name = name.rsplit('/').next().unwrap_or(name);
+        match file_name.rsplit_once('.') {
+            Some((_, extension)) => {
+                let extension = extension.to_lowercase();
+                self.extensions.contains(&extension)
+            }
+            None => self.extensions.iter().any(|e| e.is_empty()),
+        }
+    }
+
+    fn matches_size(&self, size: u64) -> bool {
+        (self.min_size, self.max_size).contains(&size)
+    }
+
+    pub fn matches(&self, file: &File) -> bool {
+        self.matches_size(file.size)
+            && self.langs.matches_file(file)
+            && self.matches_extension(&file.name)
+            && self.matches_name(&file.name)
+    }
+}
+
+impl<'a> Selection<'a> {
+    pub fn new(manifest: &'a Manifest, selector: &Selector) -> Self {
+        Self {
+            manifest,
+            files: manifest
+                .files
+                .iter()
+                .filter(|file| selector.matches(file))
+                .collect(),
+        }
+    }
+
+    pub fn select(&self, selector: &Selector) -> Self {
+        Self {
+            manifest: self.manifest,
+            files: self
+                .files
+                .iter()
+                .copied()
+                .filter(|file| selector.matches(file))
+                .collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &'a File> + '_ {
+        self.files.iter().copied()
+    }
+
+    pub fn get_total_size(&self) -> u64 {
+        self.files.iter().map(|file| file.size).sum()
+    }
+
+    pub fn verify(&self, dir: &str) -> VerifyReport<'a> {
+        VerifyReport::new(&self.files, dir)
+    }
+
+    pub fn verify_with_cache(&self, cache: &mut VerifyCache) -> VerifyReport<'a> {
+        let dir = cache.dir.clone();
+        VerifyReport::new_with_cache(&self.files, &dir, cache)
+    }
+
+    pub fn download_all(&self) -> Vec<DownloadFile> {
+        self.files.iter().map(|file| file.download_all()).collect()
+    }
+
+    pub fn download_checked_in_dir(&self, dir: &str) -> Vec<DownloadFile> {
+        self.files
+            .par_iter()
+            .map(|file| file.download_checked_in_dir(dir))
+            .collect()
+    }
+
+    pub fn repair_in_dir(
+        &self,
+        dir: &str,
+        agent: &mut ureq::Agent,
+        cdn: &str,
+    ) -> Result<VerifyReport<'a>, Error> {
+        let report = self.verify(dir);
+        report.plan_repair().download_in_dir(dir, agent, cdn)?;
+        Ok(report)
+    }
+
+    pub fn repair_in_dir_with_cache(
+        &self,
+        cache: &mut VerifyCache,
+        agent: &mut ureq::Agent,
+        cdn: &str,
+    ) -> Result<VerifyReport<'a>, Error> {
+        let report = self.verify_with_cache(cache);
+        report
+            .plan_repair()
+            .download_in_dir(&cache.dir, agent, cdn)?;
+        Ok(report)
+    }
+
+    pub fn download_checked_in_dir_with_cache(&self, cache: &VerifyCache) -> Vec<DownloadFile> {
+        self.files
+            .par_iter()
+            .map(|file| file.download_checked_in_dir_with_cache(cache))
+            .collect()
+    }
+
+    pub fn scan_in_dir(&self, dir: &str) -> Result<ScanReport, Error> {
+        ScanReport::new(self, dir)
+    }
+
+    pub fn plan_update(&self, old: &'a Manifest, dir: &str) -> UpdatePlan<'a> {
+        UpdatePlan::new(old, self, dir)
+    }
+
+    pub fn plan_download_in_dir(&self, dir: &str) -> Result<DownloadPlan, Error> {
+        self.plan_download(dir, self.download_checked_in_dir(dir))
+    }
+
+    pub fn plan_download_in_dir_with_cache(
+        &self,
+        cache: &VerifyCache,
+    ) -> Result<DownloadPlan, Error> {
+        self.plan_download(&cache.dir, self.download_checked_in_dir_with_cache(cache))
+    }
+
+    fn plan_download(&self, dir: &str, checked: Vec<DownloadFile>) -> Result<DownloadPlan, Error> {
+        let mut downloads = Vec::new();
+        for (file, download) in self.files.iter().zip(checked) {
+            let path = format!("{}/{}", dir, &file.name);
+            let stale = if file.is_link() {
+                !file.verify(dir)
+            } else {
+                !fs::metadata(&path).is_ok_and(|metadata| metadata.len() == file.size)
+            };
+            if !download.bundles.is_empty() || stale {
+                downloads.push(download);
+            } else if !file.is_link() {
+                fs::File::open(&path)
+                    .and_then(|writer| download.set_mode(&writer))
+                    .map_err(|err| Error::io("Failed to set file mode!", err).with_path(&path))?;
+            }
+        }
+        Ok(DownloadPlan::new(downloads))
+    }
+
+    pub fn download_in_dir(
+        &self,
+        dir: &str,
+        agent: &mut ureq::Agent,
+        cdn: &str,
+    ) -> Result<(), Error> {
+        self.plan_download_in_dir(dir)?
+            .download_in_dir(dir, agent, cdn)
+    }
+
+    pub fn download_in_dir_with_cache(
+        &self,
+        cache: &VerifyCache,
+        agent: &mut ureq::Agent,
+        cdn: &str,
+    ) -> Result<(), Error> {
+        self.plan_download_in_dir_with_cache(cache)?
+            .download_in_dir(&cache.dir, agent, cdn)
+    }
+}
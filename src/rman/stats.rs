@@ -0,0 +1,209 @@
+use super::{File, HashType, LangInfo, Manifest, Params};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt,
+};
+
+#[derive(Clone, Debug, Default)]
+pub struct DirStats {
+    pub name: String,
+    pub file_count: usize,
+    pub total_size: u64,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ParamsStats {
+    pub index: u8,
+    pub params: Params,
+    pub file_count: usize,
+    pub total_size: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct ManifestStats<'a> {
+    pub manifest: &'a Manifest,
+    pub file_count: usize,
+    pub link_count: usize,
+    pub dir_count: usize,
+    pub total_size: u64,
+    pub total_compressed: u64,
+    pub unique_size: u64,
+    pub bundle_count: usize,
+    pub chunk_count: usize,
+    pub chunk_refs: usize,
+    pub langs: Vec<LangInfo>,
+    pub dirs: Vec<DirStats>,
+    pub largest: Vec<&'a File>,
+    pub hash_types: BTreeMap<HashType, usize>,
+    pub params: Vec<ParamsStats>,
+}
+
+impl<'a> ManifestStats<'a> {
+    pub const LARGEST_FILES: usize = 10;
+
+    pub fn new(manifest: &'a Manifest, largest: usize) -> Self {
+        let mut result = Self {
+            manifest,
+            file_count: manifest.files.len(),
+            link_count: manifest.files.iter().filter(|file| file.is_link()).count(),
+            dir_count: manifest.dirs.len(),
+            total_size: manifest.files.iter().map(|file| file.size).sum(),
+            total_compressed: manifest
+                .chunks
+                .values()
+                .map(|chunk| chunk.size_compressed as u64)
+                .sum(),
+            unique_size: manifest
+                .chunks
+                .values()
+                .map(|chunk| chunk.size_uncompressed as u64)
+                .sum(),
+            bundle_count: manifest
+                .chunks
+                .values()
+                .map(|chunk| chunk.bundle_id)
+                .collect::<HashSet<_>>()
+                .len(),
+            chunk_count: manifest.chunks.len(),
+            chunk_refs: manifest.files.iter().map(|file| file.chunks.len()).sum(),
+            langs: manifest.get_lang_infos(),
+            dirs: Vec::new(),
+            largest: manifest.files.iter().collect(),
+            hash_types: BTreeMap::new(),
+            params: Vec::new(),
+        };
+        result
+            .largest
+            .sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+        result.largest.truncate(largest);
+        let mut dirs = BTreeMap::<&str, DirStats>::new();
+        let mut params = BTreeMap::<u8, ParamsStats>::new();
+        for file in &manifest.files {
+            let name = match file.name.split_once('/') {
+                Some((name, _)) => name,
+                None => "",
+            };
+            let dir = dirs.entry(name).or_insert_with(|| DirStats {
+                name: name.to_owned(),
+                ..Default::default()
+            });
+            dir.file_count += 1;
+            dir.total_size += file.size;
+            *result.hash_types.entry(file.hash_type).or_default() += 1;
+            let params = params
+                .entry(file.meta.params_index)
+                .or_insert_with(|| ParamsStats {
+                    index: file.meta.params_index,
+                    params: file.meta.params,
+                    ..Default::default()
+                });
+            params.file_count += 1;
+            params.total_size += file.size;
+        }
+        result.dirs = dirs.into_values().collect();
+        result.dirs.sort_by(|a, b| {
+            b.total_size
+                .cmp(&a.total_size)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        result.params = params.into_values().collect();
+        result
+    }
+
+    pub fn get_dedup_ratio(&self) -> f64 {
+        if self.unique_size == 0 {
+            1.0
+        } else {
+            self.total_size as f64 / self.unique_size as f64
+        }
+    }
+
+    pub fn get_compression_ratio(&self) -> f64 {
+        if self.total_compressed == 0 {
+            1.0
+        } else {
+            self.unique_size as f64 / self.total_compressed as f64
+        }
+    }
+}
+
+impl<'a> fmt::Display for ManifestStats<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let integrity = &self.manifest.integrity;
+        let checked = |checked| if checked { "checked" } else { "absent" };
+        writeln!(f, "manifest {:016X}", self.manifest.id)?;
+        writeln!(
+            f,
+            "body {} bytes compressed, {} bytes uncompressed, frame size {}, frame checksum {}",
+            integrity.size_compressed,
+            integrity.size_uncompressed,
+            checked(integrity.checked_content_size),
+            checked(integrity.checked_content_checksum)
+        )?;
+        writeln!(
+            f,
+            "{} files, {} links, {} dirs",
+            self.file_count, self.link_count, self.dir_count
+        )?;
+        writeln!(
+            f,
+            "{} bytes total, {} bytes unique, {} bytes compressed",
+            self.total_size, self.unique_size, self.total_compressed
+        )?;
+        writeln!(
+            f,
+            "{} bundles, {} chunks, {} chunk refs, {:.2}x dedup, {:.2}x compression",
+            self.bundle_count,
+            self.chunk_count,
+            self.chunk_refs,
+            self.get_dedup_ratio(),
+            self.get_compression_ratio()
+        )?;
+        writeln!(f, "langs:")?;
+        for info in &self.langs {
+            writeln!(
+                f,
+                "    {:<12} {:>8} files {:>14} bytes",
+                info.name(),
+                info.file_count,
+                info.total_size
+            )?;
+        }
+        writeln!(f, "dirs:")?;
+        for dir in &self.dirs {
+            writeln!(
+                f,
+                "    {:<24} {:>8} files {:>14} bytes",
+                format!("/{}", dir.name),
+                dir.file_count,
+                dir.total_size
+            )?;
+        }
+        writeln!(f, "hash types:")?;
+        for (hash_type, count) in &self.hash_types {
+            writeln!(
+                f,
+                "    {:<12} {:>8} files",
+                format!("{:?}", hash_type),
+                count
+            )?;
+        }
+        writeln!(f, "params:")?;
+        for params in &self.params {
+            writeln!(
+                f,
+                "    #{:<3} hash {} max {:>10} {:>8} files {:>14} bytes",
+                params.index,
+                params.params.hash_type,
+                params.params.max_uncompressed,
+                params.file_count,
+                params.total_size
+            )?;
+        }
+        write!(f, "largest:")?;
+        for file in &self.largest {
+            write!(f, "\n    {:>14} {}", file.size, file.name)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,276 @@
+use super::{Chunk, DownloadFile, DownloadPlan, Error, File, Manifest, Selection};
+use rayon::prelude::*;
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    fs,
+    io::{self, Read, Seek, Write},
+};
+
+#[derive(Clone, Debug)]
+pub struct LocalChunk {
+    pub name: String,
+    pub offset_source: u64,
+    pub offset_target: u64,
+    pub size: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct UpdateFile<'a> {
+    pub file: &'a File,
+    pub local: Vec<LocalChunk>,
+    pub download: DownloadFile,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct UpdatePlan<'a> {
+    pub files: Vec<UpdateFile<'a>>,
+    pub unchanged: Vec<&'a File>,
+}
+
+struct ChunkSource<'a> {
+    file: &'a File,
+    chunk: &'a Chunk,
+}
+
+struct SourceReader<'a> {
+    dir: &'a str,
+    files: HashMap<String, Option<fs::File>>,
+    buffer: Vec<u8>,
+}
+
+impl<'a> SourceReader<'a> {
+    fn new(dir: &'a str) -> Self {
+        Self {
+            dir,
+            files: HashMap::new(),
+            buffer: Vec::new(),
+        }
+    }
+
+    fn check(&mut self, source: &ChunkSource) -> bool {
+        let dir = self.dir;
+        let file = self
+            .files
+            .entry(source.file.name.clone())
+            .or_insert_with(|| fs::File::open(format!("{}/{}", dir, &source.file.name)).ok());
+        let file = match file {
+            Some(file) => file,
+            None => return false,
+        };
+        self.buffer
+            .resize(source.chunk.size_uncompressed as usize, 0u8);
+        file.seek(io::SeekFrom::Start(source.chunk.offset_uncompressed))
+            .is_ok()
+            && file.read_exact(&mut self.buffer).is_ok()
+            && source.file.hash_type.compute(&self.buffer) == source.chunk.chunk_id
+    }
+}
+
+impl<'a> UpdatePlan<'a> {
+    pub const TEMP_SUFFIX: &'static str = ".rman-tmp";
+
+    pub fn new(old: &'a Manifest, new: &Selection<'a>, dir: &str) -> Self {
+        let mut sources = HashMap::<u64, Vec<ChunkSource>>::new();
+        for file in old.files.iter().filter(|file| !file.is_link()) {
+            for chunk in &file.chunks {
+                sources
+                    .entry(chunk.chunk_id)
+                    .or_default()
+                    .push(ChunkSource { file, chunk });
+            }
+        }
+        let results = new
+            .files
+            .par_iter()
+            .map(|&file| Self::plan_file(file, &sources, dir))
+            .collect::<Vec<_>>();
+        let mut plan = Self::default();
+        for (file, update) in results {
+            match update {
+                Some(update) => plan.files.push(update),
+                None => plan.unchanged.push(file),
+            }
+        }
+        plan
+    }
+
+    fn plan_file(
+        file: &'a File,
+        sources: &HashMap<u64, Vec<ChunkSource<'_>>>,
+        dir: &str,
+    ) -> (&'a File, Option<UpdateFile<'a>>) {
+        if file.is_link() {
+            let update = if file.verify(dir) {
+                None
+            } else {
+                Some(UpdateFile {
+                    file,
+                    local: Vec::new(),
+                    download: file.download_all(),
+                })
+            };
+            return (file, update);
+        }
+        let mut reader = SourceReader::new(dir);
+        let mut local = Vec::new();
+        let mut found = HashSet::new();
+        let mut in_place = true;
+        for chunk in &file.chunks {
+            let candidates = sources.get(&chunk.chunk_id).map_or(&[][..], Vec::as_slice);
+            let same = candidates.iter().find(|source| {
+                source.file.name == file.name
+                    && source.chunk.offset_uncompressed == chunk.offset_uncompressed
+            });
+            let others = candidates.iter().filter(|source| {
+                source.file.name != file.name
+                    || source.chunk.offset_uncompressed != chunk.offset_uncompressed
+            });
+            let source = same
+                .into_iter()
+                .chain(others)
+                .find(|source| reader.check(source));
+            match source {
+                Some(source) => {
+                    in_place &= source.file.name == file.name
+                        && source.chunk.offset_uncompressed == chunk.offset_uncompressed;
+                    found.insert(chunk.offset_uncompressed);
+                    local.push(LocalChunk {
+                        name: source.file.name.clone(),
+                        offset_source: source.chunk.offset_uncompressed,
+                        offset_target: chunk.offset_uncompressed,
+                        size: chunk.size_uncompressed,
+                    });
+                }
+                None => in_place = false,
+            }
+        }
+        let path = format!("{}/{}", dir, &file.name);
+        if in_place
+            && fs::metadata(&path)
+                .is_ok_and(|metadata| metadata.len() == file.size && file.has_mode(&metadata))
+        {
+            return (file, None);
+        }
+        let download = file.download_if(|chunk| !found.contains(&chunk.offset_uncompressed));
+        (
+            file,
+            Some(UpdateFile {
+                file,
+                local,
+                download,
+            }),
+        )
+    }
+
+    pub fn get_local_size(&self) -> u64 {
+        self.files
+            .iter()
+            .flat_map(|update| update.local.iter())
+            .map(|local| local.size as u64)
+            .sum()
+    }
+
+    pub fn get_download_size(&self) -> u64 {
+        self.files
+            .iter()
+            .map(|update| update.download.get_total_size())
+            .sum()
+    }
+
+    pub fn apply_with_progress<F: FnMut(u64)>(
+        &self,
+        dir: &str,
+        agent: &mut ureq::Agent,
+        cdn: &str,
+        mut progress: F,
+    ) -> Result<(), Error> {
+        let mut temps = Vec::new();
+        let mut downloads = Vec::new();
+        let result = self
+            .files
+            .iter()
+            .filter(|update| !update.file.is_link())
+            .try_for_each(|update| {
+                let name = format!("{}{}", &update.file.name, Self::TEMP_SUFFIX);
+                temps.push(format!("{}/{}", dir, &name));
+                downloads.push(DownloadFile {
+                    name: name.clone(),
+                    ..update.download.clone()
+                });
+                update
+                    .write_local(dir, &name)
+                    .map_err(|err| err.with_path(&update.file.name))
+            })
+            .and_then(|_| {
+                DownloadPlan::new(downloads).download_in_dir_with_progress(
+                    dir,
+                    agent,
+                    cdn,
+                    &mut progress,
+                )
+            });
+        if let Err(err) = result {
+            for temp in &temps {
+                let _ = fs::remove_file(temp);
+            }
+            return Err(err);
+        }
+        for update in &self.files {
+            if update.file.is_link() {
+                update.download.download_link_in_dir(dir)?;
+            } else {
+                let path = format!("{}/{}", dir, &update.file.name);
+                let temp = format!("{}{}", &path, Self::TEMP_SUFFIX);
+                fs::rename(&temp, &path).map_err(|err| {
+                    Error::io("Failed to replace file!", err).with_path(&update.file.name)
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn apply(&self, dir: &str, agent: &mut ureq::Agent, cdn: &str) -> Result<(), Error> {
+        self.apply_with_progress(dir, agent, cdn, |_| ())
+    }
+}
+
+impl<'a> UpdateFile<'a> {
+    fn write_local(&self, dir: &str, temp: &str) -> Result<(), Error> {
+        let mut writer = DownloadFile {
+            name: temp.to_owned(),
+            ..DownloadFile::default()
+        }
+        .open_in_dir(dir)?;
+        writer
+            .set_len(0)
+            .map_err(|err| Error::io("Failed to truncate file!", err))?;
+        let mut sources = HashMap::<&str, fs::File>::new();
+        let mut buffer = Vec::new();
+        for local in &self.local {
+            let source = match sources.entry(&local.name) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    entry.insert(fs::File::open(format!("{}/{}", dir, &local.name)).map_err(
+                        |err| Error::io("Failed to open local source!", err).with_path(&local.name),
+                    )?)
+                }
+            };
+            buffer.resize(local.size as usize, 0u8);
+            source
+                .seek(io::SeekFrom::Start(local.offset_source))
+                .and_then(|_| source.read_exact(&mut buffer))
+                .map_err(|err| {
+                    Error::io("Failed to read local chunk!", err)
+                        .with_path(&local.name)
+                        .with_offset(local.offset_source)
+                })?;
+            writer
+                .seek(io::SeekFrom::Start(local.offset_target))
+                .and_then(|_| writer.write_all(&buffer))
+                .map_err(|err| {
+                    Error::io("Failed to write chunk!", err).with_offset(local.offset_target)
+                })?;
+        }
+        Ok(())
+    }
+}
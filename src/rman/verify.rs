@@ -0,0 +1,279 @@
+use super::{Chunk, DownloadFile, DownloadPlan, File, VerifyCache};
+use rayon::prelude::*;
+use std::{collections::HashSet, fmt, fs, io, path::Path};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BadChunk {
+    pub offset: u64,
+    pub size: u32,
+    pub expected_id: u64,
+    pub actual_id: Option<u64>,
+}
+
+#[derive(Clone, Debug)]
+pub struct FileVerify<'a> {
+    pub file: &'a File,
+    pub path: String,
+    pub actual_size: Option<u64>,
+    pub metadata: Option<fs::Metadata>,
+    pub unreadable: bool,
+    pub bad_chunks: Vec<BadChunk>,
+    pub bad_link: bool,
+    pub bad_mode: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct VerifyReport<'a> {
+    pub files: Vec<FileVerify<'a>>,
+}
+
+#[cfg(unix)]
+fn read_at(file: &fs::File, buffer: &mut [u8], offset: u64) -> io::Result<()> {
+    use std::os::unix::fs::FileExt;
+    file.read_exact_at(buffer, offset)
+}
+
+#[cfg(windows)]
+fn read_at(file: &fs::File, buffer: &mut [u8], offset: u64) -> io::Result<()> {
+    use std::os::windows::fs::FileExt;
+    let mut done = 0;
+    while done < buffer.len() {
+        match file.seek_read(&mut buffer[done..], offset + done as u64)? {
+            0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+            read => done += read,
+        }
+    }
+    Ok(())
+}
+
+impl BadChunk {
+    fn check(file: &File, reader: &fs::File, chunk: &Chunk, buffer: &mut Vec<u8>) -> Option<Self> {
+        buffer.resize(chunk.size_uncompressed as usize, 0u8);
+        let actual_id = read_at(reader, buffer, chunk.offset_uncompressed)
+            .ok()
+            .map(|_| file.hash_type.compute(buffer));
+        if actual_id == Some(chunk.chunk_id) {
+            None
+        } else {
+            Some(Self {
+                offset: chunk.offset_uncompressed,
+                size: chunk.size_uncompressed,
+                expected_id: chunk.chunk_id,
+                actual_id,
+            })
+        }
+    }
+}
+
+impl<'a> FileVerify<'a> {
+    fn check(file: &'a File, dir: &str, cache: Option<&VerifyCache>) -> Self {
+        let mut result = Self {
+            file,
+            path: format!("{}/{}", dir, &file.name),
+            actual_size: None,
+            metadata: None,
+            unreadable: false,
+            bad_chunks: Vec::new(),
+            bad_link: false,
+            bad_mode: false,
+        };
+        if file.is_link() {
+            if let Ok(metadata) = fs::symlink_metadata(&result.path) {
+                result.actual_size = Some(0);
+                result.bad_link = !metadata.file_type().is_symlink()
+                    || fs::read_link(&result.path)
+                        .map_or(true, |target| target != Path::new(&file.link_name));
+            }
+            return result;
+        }
+        let (reader, metadata) = match fs::File::open(&result.path)
+            .and_then(|reader| reader.metadata().map(|metadata| (reader, metadata)))
+        {
+            Ok(opened) => opened,
+            Err(err) => {
+                result.unreadable = err.kind() != io::ErrorKind::NotFound;
+                return result;
+            }
+        };
+        result.actual_size = Some(metadata.len());
+        result.bad_mode = !file.has_mode(&metadata);
+        if cache.is_some_and(|cache| cache.is_good(file, &metadata)) {
+            result.metadata = Some(metadata);
+            return result;
+        }
+        result.bad_chunks = file
+            .chunks
+            .par_iter()
+            .map_init(Vec::new, |buffer, chunk| {
+                BadChunk::check(file, &reader, chunk, buffer)
+            })
+            .flatten()
+            .collect();
+        result.bad_chunks.sort_by_key(|bad| bad.offset);
+        result.metadata = Some(metadata);
+        result
+    }
+
+    pub fn is_missing(&self) -> bool {
+        self.actual_size.is_none() && !self.unreadable
+    }
+
+    pub fn is_size_mismatch(&self) -> bool {
+        !self.file.is_link() && self.actual_size.is_some_and(|size| size != self.file.size)
+    }
+
+    pub fn get_trailing_size(&self) -> u64 {
+        match self.actual_size {
+            Some(size) if !self.file.is_link() && size > self.file.size => size - self.file.size,
+            _ => 0,
+        }
+    }
+
+    pub fn download(&self) -> DownloadFile {
+        if self.is_missing() || self.file.is_link() {
+            return self.file.download_all();
+        }
+        let bad = self
+            .bad_chunks
+            .iter()
+            .map(|bad| bad.offset)
+            .collect::<HashSet<_>>();
+        self.file
+            .download_if(|chunk| bad.contains(&chunk.offset_uncompressed))
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.actual_size.is_some()
+            && !self.is_size_mismatch()
+            && !self.bad_link
+            && !self.bad_mode
+            && self.bad_chunks.is_empty()
+    }
+}
+
+impl<'a> VerifyReport<'a> {
+    const OPEN_FILES: usize = 64;
+
+    pub fn new(files: &[&'a File], dir: &str) -> Self {
+        Self::check(files, dir, None)
+    }
+
+    pub fn new_with_cache(files: &[&'a File], dir: &str, cache: &mut VerifyCache) -> Self {
+        let result = Self::check(files, dir, Some(cache));
+        cache.update(&result);
+        result
+    }
+
+    fn check(files: &[&'a File], dir: &str, cache: Option<&VerifyCache>) -> Self {
+        let mut result = Self {
+            files: Vec::with_capacity(files.len()),
+        };
+        for batch in files.chunks(Self::OPEN_FILES) {
+            let checked = batch
+                .par_iter()
+                .map(|&file| FileVerify::check(file, dir, cache))
+                .collect::<Vec<_>>();
+            result.files.extend(checked);
+        }
+        result
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.files.iter().all(FileVerify::is_ok)
+    }
+
+    pub fn plan_repair(&self) -> DownloadPlan {
+        DownloadPlan::new(
+            self.get_bad_files()
+                .filter(|result| !result.unreadable)
+                .map(|result| result.download())
+                .collect(),
+        )
+    }
+
+    pub fn get_good_files(&self) -> impl Iterator<Item = &FileVerify<'a>> {
+        self.files.iter().filter(|result| result.is_ok())
+    }
+
+    pub fn get_bad_files(&self) -> impl Iterator<Item = &FileVerify<'a>> {
+        self.files.iter().filter(|result| !result.is_ok())
+    }
+
+    pub fn get_missing_files(&self) -> impl Iterator<Item = &FileVerify<'a>> {
+        self.files.iter().filter(|result| result.is_missing())
+    }
+
+    pub fn get_unreadable_files(&self) -> impl Iterator<Item = &FileVerify<'a>> {
+        self.files.iter().filter(|result| result.unreadable)
+    }
+
+    pub fn get_size_mismatches(&self) -> impl Iterator<Item = &FileVerify<'a>> {
+        self.files.iter().filter(|result| result.is_size_mismatch())
+    }
+
+    pub fn get_bad_chunk_count(&self) -> usize {
+        self.files
+            .iter()
+            .map(|result| result.bad_chunks.len())
+            .sum()
+    }
+}
+
+impl<'a> fmt::Display for FileVerify<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_ok() {
+            return write!(f, "{} is correct!", self.file.name);
+        }
+        if self.is_missing() {
+            return write!(f, "{} is missing!", self.file.name);
+        }
+        if self.unreadable {
+            return write!(f, "{} is unreadable!", self.file.name);
+        }
+        write!(f, "{} is bad!", self.file.name)?;
+        if self.bad_link {
+            write!(f, "\n    link does not point to {}", self.file.link_name)?;
+        }
+        if self.bad_mode {
+            write!(f, "\n    mode is not {}", self.file.get_mode_string())?;
+        }
+        if let Some(size) = self.actual_size.filter(|_| self.is_size_mismatch()) {
+            write!(f, "\n    size {} expected {}", size, self.file.size)?;
+        }
+        if self.get_trailing_size() != 0 {
+            write!(f, "\n    {} trailing bytes", self.get_trailing_size())?;
+        }
+        for bad in &self.bad_chunks {
+            write!(
+                f,
+                "\n    chunk {:016X} at {}..{}",
+                bad.expected_id,
+                bad.offset,
+                bad.offset + bad.size as u64
+            )?;
+            match bad.actual_id {
+                Some(actual_id) => write!(f, " hashes to {:016X}", actual_id)?,
+                None => write!(f, " is unreadable")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a> fmt::Display for VerifyReport<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for result in &self.files {
+            writeln!(f, "{}", result)?;
+        }
+        write!(
+            f,
+            "{} files, {} bad, {} missing, {} unreadable, {} size mismatches, {} bad chunks",
+            self.files.len(),
+            self.get_bad_files().count(),
+            self.get_missing_files().count(),
+            self.get_unreadable_files().count(),
+            self.get_size_mismatches().count(),
+            self.get_bad_chunk_count()
+        )
+    }
+}